@@ -6,8 +6,10 @@ fn main() {
         Windows::Win32::Foundation::*,
         Windows::Win32::System::Threading::*,
         Windows::Win32::Graphics::Dwm::*,
+        Windows::Win32::Graphics::Gdi::*,
         Windows::Win32::UI::Accessibility::*,
         Windows::Win32::UI::WindowsAndMessaging::*,
         Windows::Win32::UI::Input::KeyboardAndMouse::*,
+        Windows::Win32::UI::HiDpi::*,
     };
 }
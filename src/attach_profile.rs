@@ -0,0 +1,515 @@
+use crate::auto_attach::{AutoAttach, AutoAttachSession};
+use crate::win2::{backend::{Win32System, WindowSystem}, dpi::Unit, error::Result, point::Offset, rect::Insets, selector::WindowSelector};
+use crate::window_attach::{Anchor, AttachDirection, AttachMode, AttachOptions, SizeConstraints};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/*
+ * attaches described in a config file, one `attach` entry per
+ * companion, the keys are the options of `WindowAttach`:
+ *
+ *   [[attach]]
+ *   name = "wechat-panel"
+ *   target = { exe = "WeChat.exe", class = "WeChatMainWndForPC" }
+ *   companion = { title = "panel" }
+ *   dir = "right_top"            # or anchor = { target = [1.0, 0.5], companion = [0.0, 0.5] }
+ *   offset = [8, 0]
+ *   unit = "logical"             # physical by default
+ *   match_size = true
+ *   size = { match_height = 0.5, min_height = 200, aspect_ratio = 0.75 }
+ *   keep_in_work_area = true
+ *   auto_flip = true
+ *   mode = "inside"              # outside, inside or fill
+ *   insets = [0, 8, 8, 0]        # left, top, right, bottom
+ *   click_through = false
+ *   coalesce_ms = 16             # at most one reposition per interval
+ *
+ * the same in JSON is `{ "attach": [ { "name": "wechat-panel", ... } ] }`.
+ */
+
+// a parsed entry, ready to run
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachProfile {
+    pub name: Option<String>,
+    pub target: WindowSelector,
+    pub companion: WindowSelector,
+    pub opts: AttachOptions,
+    // the default of `WindowAttach` when not set
+    pub coalesce: Option<Duration>,
+}
+
+impl AttachProfile {
+
+    pub fn auto_attach(&self) -> Result<AutoAttach> {
+        self.auto_attach_with_system(Win32System::shared())
+    }
+
+    // the companion must be there already, the target may show up later
+    pub fn auto_attach_with_system(&self, sys: Arc<dyn WindowSystem>) -> Result<AutoAttach> {
+        let companion = self.companion.find(&*sys).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("{}: companion window not found", self.label()))
+        })?;
+
+        let mut auto = AutoAttach::with_system(self.target.clone(), companion, sys);
+        auto.options(self.opts);
+        if let Some(interval) = self.coalesce {
+            auto.coalesce(interval);
+        }
+        Ok(auto)
+    }
+
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("attach \"{}\"", name),
+            None => "attach".to_string(),
+        }
+    }
+}
+
+// all entries of a file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AttachProfiles {
+    pub entries: Vec<AttachProfile>,
+}
+
+impl AttachProfiles {
+
+    pub fn from_toml_str(s: &str) -> std::result::Result<Self, ProfileError> {
+        let doc: toml::Value = toml::from_str(s).map_err(|e| ProfileError::Parse(e.to_string()))?;
+        let doc = serde_json::to_value(doc).map_err(|e| ProfileError::Parse(e.to_string()))?;
+        Self::from_value(doc)
+    }
+
+    pub fn from_json_str(s: &str) -> std::result::Result<Self, ProfileError> {
+        let doc: Value = serde_json::from_str(s).map_err(|e| ProfileError::Parse(e.to_string()))?;
+        Self::from_value(doc)
+    }
+
+    // `.json` is read as JSON, anything else as TOML
+    pub fn load<P: AsRef<Path>>(path: P) -> std::result::Result<Self, ProfileError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ProfileError::Parse(format!("{}: {}", path.display(), e)))?;
+
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            Self::from_json_str(&text)
+        } else {
+            Self::from_toml_str(&text)
+        }
+    }
+
+    fn from_value(doc: Value) -> std::result::Result<Self, ProfileError> {
+        let mut doc = match doc {
+            Value::Object(map) => map,
+            _ => return Err(ProfileError::Parse("expected a table with an `attach` list".to_string())),
+        };
+
+        if let Some(key) = doc.keys().find(|k| *k != "attach") {
+            return Err(ProfileError::Parse(format!("unknown key `{}`, expected `attach`", key)));
+        }
+
+        let entries = match doc.remove("attach") {
+            Some(Value::Array(entries)) => entries,
+            None => vec![],
+            Some(_) => return Err(ProfileError::Parse("`attach` must be a list".to_string())),
+        };
+
+        // each entry on its own, so errors can tell which one is bad
+        let entries = entries.into_iter().enumerate().map(|(index, entry)| {
+            let name = entry.get("name").and_then(Value::as_str).map(str::to_string);
+            let error = |message: String| ProfileError::Entry { index, name: name.clone(), message };
+
+            let raw: RawEntry = serde_json::from_value(entry).map_err(|e| error(e.to_string()))?;
+            raw.into_profile().map_err(error)
+        }).collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Self { entries })
+    }
+
+    // start the auto attach of every entry
+    pub fn start(&self) -> Result<Vec<AutoAttachSession>> {
+        self.start_with_system(Win32System::shared())
+    }
+
+    pub fn start_with_system(&self, sys: Arc<dyn WindowSystem>) -> Result<Vec<AutoAttachSession>> {
+        self.entries.iter()
+            .map(|profile| profile.auto_attach_with_system(sys.clone())?.start())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileError {
+    // not a valid TOML or JSON document
+    Parse(String),
+    // the entry at `index` of `attach`, counted from 0
+    Entry { index: usize, name: Option<String>, message: String },
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(message) => write!(f, "invalid attach profile: {}", message),
+            Self::Entry { index, name: Some(name), message } => write!(f, "attach[{}] \"{}\": {}", index, name, message),
+            Self::Entry { index, name: None, message } => write!(f, "attach[{}]: {}", index, message),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<ProfileError> for Error {
+    fn from(e: ProfileError) -> Self {
+        Error::new(ErrorKind::InvalidData, e)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEntry {
+    name: Option<String>,
+    target: RawSelector,
+    companion: RawSelector,
+    dir: Option<RawDirection>,
+    anchor: Option<RawAnchor>,
+    #[serde(default)]
+    offset: [i32; 2],
+    #[serde(default)]
+    unit: RawUnit,
+    #[serde(default)]
+    match_size: bool,
+    #[serde(default)]
+    size: RawSize,
+    #[serde(default)]
+    keep_in_work_area: bool,
+    #[serde(default)]
+    auto_flip: bool,
+    #[serde(default)]
+    mode: RawMode,
+    insets: Option<[i32; 4]>,
+    #[serde(default)]
+    click_through: bool,
+    coalesce_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSelector {
+    class: Option<String>,
+    title: Option<String>,
+    exe: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum RawDirection {
+    LeftTop, TopLeft,
+    RightTop, TopRight,
+    RightBottom, BottomRight,
+    LeftBottom, BottomLeft,
+    LeftCenter, TopCenter,
+    RightCenter, BottomCenter,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAnchor {
+    target: [f64; 2],
+    companion: [f64; 2],
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum RawUnit {
+    #[default]
+    Physical,
+    Logical,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum RawMode {
+    #[default]
+    Outside,
+    Inside,
+    Fill,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawSize {
+    match_width: Option<f64>,
+    match_height: Option<f64>,
+    min_width: Option<i32>,
+    max_width: Option<i32>,
+    min_height: Option<i32>,
+    max_height: Option<i32>,
+    aspect_ratio: Option<f64>,
+}
+
+impl RawEntry {
+    fn into_profile(self) -> std::result::Result<AttachProfile, String> {
+        let target = self.target.into_selector("target")?;
+        let companion = self.companion.into_selector("companion")?;
+
+        let dir = match (self.dir, self.anchor) {
+            (Some(_), Some(_)) => return Err("`dir` and `anchor` can't be both set".to_string()),
+            (Some(dir), None) => dir.into(),
+            (None, Some(anchor)) => anchor.into_direction()?,
+            (None, None) => AttachDirection::RightTop,
+        };
+
+        let mode = match (self.mode, self.insets) {
+            (RawMode::Outside, Some(_)) => return Err("`insets` only apply to mode `inside` and `fill`".to_string()),
+            (RawMode::Outside, None) => AttachMode::Outside,
+            (RawMode::Inside, insets) => AttachMode::Inside(to_insets(insets)),
+            (RawMode::Fill, insets) => AttachMode::Fill(to_insets(insets)),
+        };
+
+        let opts = AttachOptions {
+            dir,
+            match_size: self.match_size,
+            size: self.size.into_constraints()?,
+            fix_pos: Offset::new(self.offset[0], self.offset[1]),
+            unit: match self.unit {
+                RawUnit::Physical => Unit::Physical,
+                RawUnit::Logical => Unit::Logical,
+            },
+            keep_in_work_area: self.keep_in_work_area,
+            auto_flip: self.auto_flip,
+            mode,
+            click_through: self.click_through,
+        };
+
+        let coalesce = self.coalesce_ms.map(Duration::from_millis);
+
+        Ok(AttachProfile { name: self.name, target, companion, opts, coalesce })
+    }
+}
+
+impl RawSelector {
+    fn into_selector(self, key: &str) -> std::result::Result<WindowSelector, String> {
+        let selector = WindowSelector { class: self.class, title: self.title, exe: self.exe };
+
+        // would match any window
+        if selector == WindowSelector::default() {
+            return Err(format!("`{}` needs at least one of `class`, `title` or `exe`", key));
+        }
+        Ok(selector)
+    }
+}
+
+impl From<RawDirection> for AttachDirection {
+    fn from(dir: RawDirection) -> Self {
+        match dir {
+            RawDirection::LeftTop => Self::LeftTop,
+            RawDirection::TopLeft => Self::TopLeft,
+            RawDirection::RightTop => Self::RightTop,
+            RawDirection::TopRight => Self::TopRight,
+            RawDirection::RightBottom => Self::RightBottom,
+            RawDirection::BottomRight => Self::BottomRight,
+            RawDirection::LeftBottom => Self::LeftBottom,
+            RawDirection::BottomLeft => Self::BottomLeft,
+            RawDirection::LeftCenter => Self::LeftCenter,
+            RawDirection::TopCenter => Self::TopCenter,
+            RawDirection::RightCenter => Self::RightCenter,
+            RawDirection::BottomCenter => Self::BottomCenter,
+        }
+    }
+}
+
+impl RawAnchor {
+    fn into_direction(self) -> std::result::Result<AttachDirection, String> {
+        let points = self.target.iter().chain(self.companion.iter());
+        if points.clone().any(|v| !v.is_finite()) {
+            return Err("`anchor` points must be finite numbers".to_string());
+        }
+
+        let anchor = Anchor::new((self.target[0], self.target[1]), (self.companion[0], self.companion[1]));
+        Ok(AttachDirection::from_anchor(anchor))
+    }
+}
+
+impl RawSize {
+    fn into_constraints(self) -> std::result::Result<SizeConstraints, String> {
+        for (key, v) in [("match_width", self.match_width), ("match_height", self.match_height), ("aspect_ratio", self.aspect_ratio)] {
+            if let Some(v) = v {
+                if !(v.is_finite() && v > 0.0) {
+                    return Err(format!("`size.{}` must be greater than 0, got {}", key, v));
+                }
+            }
+        }
+
+        for (axis, min, max) in [("width", self.min_width, self.max_width), ("height", self.min_height, self.max_height)] {
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err(format!("`size.min_{0}` {1} is larger than `size.max_{0}` {2}", axis, min, max));
+                }
+            }
+        }
+
+        Ok(SizeConstraints {
+            match_width: self.match_width,
+            match_height: self.match_height,
+            min_width: self.min_width,
+            max_width: self.max_width,
+            min_height: self.min_height,
+            max_height: self.max_height,
+            aspect_ratio: self.aspect_ratio,
+        })
+    }
+}
+
+fn to_insets(insets: Option<[i32; 4]>) -> Insets {
+    insets.map_or_else(Insets::zero, |[left, top, right, bottom]| Insets::new(left, top, right, bottom))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::win2::{backend::WindowSystem, dpi::Unit, fake::{FakeDesktop, FakeWindow}, point::Offset, rect::{Insets, Rect}, selector::WindowSelector};
+    use crate::window_attach::{AttachDirection, AttachMode, AttachOptions, SizeConstraints};
+
+    use super::{AttachProfiles, ProfileError};
+
+    const TOML: &str = r#"
+        [[attach]]
+        name = "wechat-panel"
+        target = { exe = "WeChat.exe", class = "WeChatMainWndForPC" }
+        companion = { title = "panel" }
+        dir = "left_center"
+        offset = [-8, 0]
+        unit = "logical"
+        size = { match_height = 0.5, min_height = 200 }
+        auto_flip = true
+
+        [[attach]]
+        target = { title = "Notepad" }
+        companion = { class = "Overlay" }
+        anchor = { target = [1.0, 0.3], companion = [0.0, 0.5] }
+        mode = "fill"
+        insets = [1, 2, 3, 4]
+        click_through = true
+        coalesce_ms = 50
+    "#;
+
+    #[test]
+    fn test_parse_toml_and_json() {
+        let profiles = AttachProfiles::from_toml_str(TOML).unwrap();
+        assert_eq!(profiles.entries.len(), 2);
+
+        let panel = &profiles.entries[0];
+        assert_eq!(panel.name.as_deref(), Some("wechat-panel"));
+        assert_eq!(panel.target, WindowSelector::new().with_exe("WeChat.exe").with_class("WeChatMainWndForPC"));
+        assert_eq!(panel.companion, WindowSelector::new().with_title("panel"));
+        assert_eq!(panel.opts, AttachOptions {
+            dir: AttachDirection::LeftCenter,
+            fix_pos: Offset::new(-8, 0),
+            unit: Unit::Logical,
+            size: SizeConstraints { match_height: Some(0.5), min_height: Some(200), ..Default::default() },
+            auto_flip: true,
+            ..Default::default()
+        });
+        assert_eq!(panel.coalesce, None);
+
+        let overlay = &profiles.entries[1];
+        assert_eq!(overlay.name, None);
+        assert_eq!(overlay.opts.dir, AttachDirection::from_anchor(super::Anchor::new((1.0, 0.3), (0.0, 0.5))));
+        assert_eq!(overlay.opts.mode, AttachMode::Fill(Insets::new(1, 2, 3, 4)));
+        assert!(overlay.opts.click_through);
+        assert_eq!(overlay.coalesce, Some(Duration::from_millis(50)));
+
+        let json = r#"{ "attach": [
+            { "name": "wechat-panel",
+              "target": { "exe": "WeChat.exe", "class": "WeChatMainWndForPC" },
+              "companion": { "title": "panel" },
+              "dir": "left_center", "offset": [-8, 0], "unit": "logical",
+              "size": { "match_height": 0.5, "min_height": 200 }, "auto_flip": true }
+        ] }"#;
+        assert_eq!(AttachProfiles::from_json_str(json).unwrap().entries[0], *panel);
+
+        // no entries is fine
+        assert_eq!(AttachProfiles::from_toml_str("").unwrap().entries, vec![]);
+    }
+
+    #[test]
+    fn test_errors_point_at_entry() {
+        let entry = |name: &str, rest: &str| format!(
+            "[[attach]]\ntarget = {{ title = \"a\" }}\ncompanion = {{ title = \"b\" }}\n\n[[attach]]\nname = \"{}\"\n{}\n", name, rest
+        );
+
+        let cases = [
+            (entry("bad", "target = { title = \"a\" }\ncompanion = { title = \"b\" }\ndir = \"right_up\""),
+                "attach[1] \"bad\": unknown variant `right_up`"),
+            (entry("bad", "target = {}\ncompanion = { title = \"b\" }"),
+                "attach[1] \"bad\": `target` needs at least one of `class`, `title` or `exe`"),
+            (entry("bad", "target = { title = \"a\" }\ncompanion = { title = \"b\" }\ndri = \"left_top\""),
+                "attach[1] \"bad\": unknown field `dri`"),
+            (entry("bad", "target = { title = \"a\" }"),
+                "attach[1] \"bad\": missing field `companion`"),
+            (entry("bad", "target = { title = \"a\" }\ncompanion = { title = \"b\" }\ndir = \"left_top\"\nanchor = { target = [0.0, 0.0], companion = [1.0, 1.0] }"),
+                "attach[1] \"bad\": `dir` and `anchor` can't be both set"),
+            (entry("bad", "target = { title = \"a\" }\ncompanion = { title = \"b\" }\nsize = { min_width = 300, max_width = 200 }"),
+                "attach[1] \"bad\": `size.min_width` 300 is larger than `size.max_width` 200"),
+            (entry("bad", "target = { title = \"a\" }\ncompanion = { title = \"b\" }\nsize = { aspect_ratio = 0.0 }"),
+                "attach[1] \"bad\": `size.aspect_ratio` must be greater than 0, got 0"),
+            (entry("bad", "target = { title = \"a\" }\ncompanion = { title = \"b\" }\ninsets = [1, 1, 1, 1]"),
+                "attach[1] \"bad\": `insets` only apply to mode `inside` and `fill`"),
+            ("[[attach]]\ncompanion = { title = \"b\" }".to_string(),
+                "attach[0]: missing field `target`"),
+            ("attach = 1".to_string(), "invalid attach profile: `attach` must be a list"),
+            ("[[attch]]".to_string(), "invalid attach profile: unknown key `attch`, expected `attach`"),
+        ];
+
+        for (text, expected) in cases.iter() {
+            let err = AttachProfiles::from_toml_str(text).unwrap_err().to_string();
+            assert!(err.starts_with(expected), "{:?} should start with {:?}", err, expected);
+        }
+
+        // syntax errors come with the line
+        let err = AttachProfiles::from_toml_str("[[attach]\n").unwrap_err();
+        assert!(matches!(&err, ProfileError::Parse(msg) if msg.contains("line 1")), "{}", err);
+        let err = AttachProfiles::from_json_str("{ \"attach\": [ }").unwrap_err();
+        assert!(matches!(&err, ProfileError::Parse(msg) if msg.contains("line 1")), "{}", err);
+    }
+
+    #[test]
+    fn test_run_profiles() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let panel = desktop.create(FakeWindow::new("Panel", "panel", Rect::new(0, 0, 100, 100)));
+        let target = desktop.create(FakeWindow::new("Notepad", "Notepad", Rect::new(200, 200, 400, 400)));
+
+        let profiles = AttachProfiles::from_toml_str(r#"
+            [[attach]]
+            target = { class = "Notepad" }
+            companion = { title = "panel" }
+            dir = "bottom_left"
+            match_size = true
+        "#).unwrap();
+
+        let session = profiles.entries[0].auto_attach_with_system(desktop.clone()).unwrap().bind().unwrap();
+        assert_eq!(session.target(), Some(target));
+        assert_eq!(desktop.rect(panel.hwnd).unwrap(), Rect::new(200, 600, 400, 100));
+
+        // the companion must exist
+        let missing = AttachProfiles::from_toml_str(r#"
+            [[attach]]
+            name = "ghost"
+            target = { class = "Notepad" }
+            companion = { title = "nope" }
+        "#).unwrap();
+        let err = missing.entries[0].auto_attach_with_system(desktop.clone()).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "attach \"ghost\": companion window not found");
+    }
+}
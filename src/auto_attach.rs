@@ -0,0 +1,423 @@
+use crate::win2::{backend::{Win32System, WindowSystem}, error::Result, selector::WindowSelector, window::Window, event_filter::EventFilter, window_event::{WinEvent, WinEventListener, WinEventType}};
+use crate::window_attach::{AttachOptions, AttachSession, WindowAttach};
+
+use std::io::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// attach a companion to the target window once it shows up.
+//
+// The desktop is watched for windows matching the selector, the
+// companion is attached to the first one. When it is destroyed the
+// companion moves on to another matching one, or is hidden until
+// the next one shows up, e.g. after the app was restarted.
+pub struct AutoAttach {
+    selector: WindowSelector,
+    companion: Window,
+    opts: AttachOptions,
+    interval: Option<Duration>,
+
+    sys: Arc<dyn WindowSystem>,
+
+    on_attached: Option<TargetCallback>,
+    on_detached: Option<TargetCallback>,
+    on_error: Option<ErrorCallback>,
+}
+
+pub type TargetCallback = Arc<dyn Fn(Window) + Send + Sync>;
+pub type ErrorCallback = Arc<dyn Fn(Window, &Error) + Send + Sync>;
+
+impl AutoAttach {
+
+    pub fn new(selector: WindowSelector, companion: Window) -> Self {
+        Self::with_system(selector, companion, Win32System::shared())
+    }
+
+    pub fn with_system(selector: WindowSelector, companion: Window, sys: Arc<dyn WindowSystem>) -> Self {
+        Self {
+            selector, companion,
+            opts: AttachOptions::default(),
+            interval: None,
+            sys,
+            on_attached: None,
+            on_detached: None,
+            on_error: None,
+        }
+    }
+
+    // options of each attach
+    pub fn options(&mut self, opts: AttachOptions) -> &mut Self {
+        self.opts = opts;
+        self
+    }
+
+    // coalesce interval of each attach, see `WindowAttach::coalesce`
+    pub fn coalesce(&mut self, interval: Duration) -> &mut Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    // called with the new target after attached
+    pub fn on_attached<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(Window) + Send + Sync + 'static
+    {
+        self.on_attached = Some(Arc::new(cb));
+        self
+    }
+
+    // called with the old target after it was destroyed
+    pub fn on_detached<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(Window) + Send + Sync + 'static
+    {
+        self.on_detached = Some(Arc::new(cb));
+        self
+    }
+
+    // called with the target when attaching to it failed, the
+    // companion stays hidden until the next one shows up
+    pub fn on_error<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(Window, &Error) + Send + Sync + 'static
+    {
+        self.on_error = Some(Arc::new(cb));
+        self
+    }
+
+    // watch in a background thread until the session is dropped
+    pub fn start(&mut self) -> Result<AutoAttachSession> {
+        let mut session = self.bind()?;
+        session.listener.start(false)?;
+
+        Ok(session)
+    }
+
+    // attach to a matching window if there is one already and
+    // install the hook, events are processed by `pump` of the session.
+    // Fails when attaching to the one already there fails.
+    pub fn bind(&mut self) -> Result<AutoAttachSession> {
+        let state = Arc::new(AutoState {
+            selector: self.selector.clone(),
+            companion: self.companion,
+            opts: Mutex::new(self.opts),
+            interval: self.interval,
+            sys: self.sys.clone(),
+            current: Mutex::new(None),
+            on_attached: self.on_attached.clone(),
+            on_detached: self.on_detached.clone(),
+            on_error: self.on_error.clone(),
+        });
+
+        // the target may be running before us
+        match state.find(None) {
+            Some(w) => state.attach(w)?,
+            None => state.sys.set_visible(state.companion.hwnd, false),
+        }
+
+        // hwnd 0 gets the events of all windows
+        let (_create, _show, _destroy, _idle) = (state.clone(), state.clone(), state.clone(), state.clone());
+        let mut listener = WinEventListener::with_system(Window::default(), self.sys.clone());
+        listener.filter(EventFilter::WindowObject);
+        // the title may be set after create, check again on show
+        listener.on(WinEventType::Create, move |evt: &WinEvent| _create.try_attach(evt.window));
+        listener.on(WinEventType::Show, move |evt: &WinEvent| _show.try_attach(evt.window));
+        listener.on(WinEventType::Destroy, move |evt: &WinEvent| _destroy.detach(evt.window));
+        // the attach has its own hook, run it on our thread
+        listener.on_idle(move || _idle.pump());
+        listener.install()?;
+
+        Ok(AutoAttachSession { state, listener })
+    }
+}
+
+struct AutoState {
+    selector: WindowSelector,
+    companion: Window,
+    opts: Mutex<AttachOptions>,
+    interval: Option<Duration>,
+
+    sys: Arc<dyn WindowSystem>,
+
+    // the target and the attach to it
+    current: Mutex<Option<(Window, AttachSession)>>,
+
+    on_attached: Option<TargetCallback>,
+    on_detached: Option<TargetCallback>,
+    on_error: Option<ErrorCallback>,
+}
+
+impl AutoState {
+
+    // a matching window other than the companion and `gone`
+    fn find(&self, gone: Option<Window>) -> Option<Window> {
+        let mut found = None;
+        self.sys.enum_windows(&mut |w: Window| {
+            if w != self.companion && Some(w) != gone && self.selector.matches(&*self.sys, w.hwnd) {
+                found = Some(w);
+                return false;
+            }
+            true
+        });
+
+        found
+    }
+
+    // attach to the window when it matches and there is no target
+    fn attach(&self, w: Window) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        if current.is_some() || w == self.companion || !self.selector.matches(&*self.sys, w.hwnd) {
+            return Ok(());
+        }
+
+        let opts = *self.opts.lock().unwrap();
+        let mut attach = WindowAttach::with_system(self.companion, w, self.sys.clone());
+        attach.options(opts);
+        if let Some(interval) = self.interval {
+            attach.coalesce(interval);
+        }
+        let session = attach.bind()?;
+        *current = Some((w, session));
+        drop(current);
+
+        if let Some(cb) = &self.on_attached {
+            cb(w);
+        }
+        Ok(())
+    }
+
+    // from the hook, errors go to the callback
+    fn try_attach(&self, w: Window) {
+        if let Err(e) = self.attach(w) {
+            if let Some(cb) = &self.on_error {
+                cb(w, &e);
+            }
+        }
+    }
+
+    // the target is gone, move on to another one or wait for the next
+    fn detach(&self, w: Window) {
+        let mut current = self.current.lock().unwrap();
+        if !matches!(&*current, Some((target, _)) if *target == w) {
+            return;
+        }
+
+        // restores owner and place of companion
+        current.take();
+        drop(current);
+
+        if let Some(cb) = &self.on_detached {
+            cb(w);
+        }
+
+        if let Some(next) = self.find(Some(w)) {
+            self.try_attach(next);
+        }
+
+        // none there or attaching failed
+        if self.current.lock().unwrap().is_none() {
+            self.sys.set_visible(self.companion.hwnd, false);
+        }
+    }
+
+    fn pump(&self) {
+        if let Some((_, session)) = &*self.current.lock().unwrap() {
+            session.pump();
+        }
+    }
+}
+
+// handle of a running auto attach, dropping it detaches the companion
+pub struct AutoAttachSession {
+    state: Arc<AutoState>,
+    listener: WinEventListener,
+}
+
+impl AutoAttachSession {
+
+    // the window attached to now
+    pub fn target(&self) -> Option<Window> {
+        self.state.current.lock().unwrap().as_ref().map(|(w, _)| *w)
+    }
+
+    // change options of the current and all later attaches
+    pub fn set_options(&self, opts: AttachOptions) {
+        *self.state.opts.lock().unwrap() = opts;
+        if let Some((_, session)) = &*self.state.current.lock().unwrap() {
+            session.set_options(opts);
+        }
+    }
+
+    pub fn options(&self) -> AttachOptions {
+        *self.state.opts.lock().unwrap()
+    }
+
+    // process queued events on current thread, for sessions from `bind`
+    pub fn pump(&self) -> usize {
+        self.listener.pump()
+    }
+
+    // stop watching and detach, same as dropping the session
+    pub fn detach(self) {}
+}
+
+impl Drop for AutoAttachSession {
+    fn drop(&mut self) {
+        self.state.current.lock().unwrap().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::win2::{backend::WindowSystem, fake::{FakeDesktop, FakeWindow}, rect::Rect, selector::WindowSelector};
+    use crate::window_attach::{AttachDirection, AttachOptions};
+
+    use super::AutoAttach;
+
+    fn wechat(class: &str) -> FakeWindow {
+        FakeWindow {
+            exe_path: "C:\\Program Files\\Tencent\\WeChat\\WeChat.exe".to_string(),
+            ..FakeWindow::new(class, "微信", Rect::new(100, 100, 400, 400))
+        }
+    }
+
+    #[test]
+    fn test_auto_attach_lifecycle() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let panel = desktop.create(FakeWindow::new("Panel", "panel", Rect::new(0, 0, 200, 300)));
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let (_attached, _detached) = (events.clone(), events.clone());
+
+        let selector = WindowSelector::new().with_exe("WeChat.exe").with_class("WeChatMainWndForPC");
+        let session = AutoAttach::with_system(selector, panel, desktop.clone())
+            .options(AttachOptions { dir: AttachDirection::RightTop, ..Default::default() })
+            .on_attached(move |w| _attached.lock().unwrap().push(("attached", w.hwnd)))
+            .on_detached(move |w| _detached.lock().unwrap().push(("detached", w.hwnd)))
+            .bind()
+            .unwrap();
+
+        // nothing to attach to yet
+        assert_eq!(session.target(), None);
+        assert!(!desktop.is_visible(panel.hwnd));
+
+        // the login window doesn't match
+        let login = desktop.create(wechat("WeChatLoginWndForPC"));
+        session.pump();
+        assert_eq!(session.target(), None);
+
+        desktop.destroy(login.hwnd);
+        let main = desktop.create(wechat("WeChatMainWndForPC"));
+        session.pump();
+        assert_eq!(session.target(), Some(main));
+        assert!(desktop.is_visible(panel.hwnd));
+        assert_eq!(desktop.rect(panel.hwnd).unwrap(), Rect::new(500, 100, 200, 300));
+
+        // follows the target with the inner attach
+        desktop.move_to(main.hwnd, Rect::new(200, 100, 400, 400));
+        session.pump();
+        assert_eq!(desktop.rect(panel.hwnd).unwrap(), Rect::new(600, 100, 200, 300));
+
+        // the app is restarted
+        desktop.destroy(main.hwnd);
+        session.pump();
+        assert_eq!(session.target(), None);
+        assert!(!desktop.is_visible(panel.hwnd));
+
+        let again = desktop.create(wechat("WeChatMainWndForPC"));
+        session.pump();
+        assert_eq!(session.target(), Some(again));
+        assert_eq!(desktop.rect(panel.hwnd).unwrap(), Rect::new(500, 100, 200, 300));
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            ("attached", main.hwnd),
+            ("detached", main.hwnd),
+            ("attached", again.hwnd),
+        ]);
+
+        session.detach();
+        assert_eq!(desktop.owner(panel.hwnd), Default::default());
+    }
+
+    #[test]
+    fn test_auto_attach_existing_target() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let main = desktop.create(wechat("WeChatMainWndForPC"));
+        let panel = desktop.create(FakeWindow::new("Panel", "panel", Rect::new(0, 0, 200, 300)));
+
+        let session = AutoAttach::with_system(WindowSelector::new().with_class("WeChatMainWndForPC"), panel, desktop.clone())
+            .bind()
+            .unwrap();
+        assert_eq!(session.target(), Some(main));
+        assert_eq!(desktop.owner(panel.hwnd), main.hwnd);
+
+        // a second one is ignored while attached
+        let second = desktop.create(wechat("WeChatMainWndForPC"));
+        session.pump();
+        assert_eq!(session.target(), Some(main));
+
+        session.set_options(AttachOptions { dir: AttachDirection::LeftTop, ..Default::default() });
+        assert_eq!(desktop.rect(panel.hwnd).unwrap(), Rect::new(-100, 100, 200, 300));
+
+        // and taken over when the first one goes away
+        desktop.destroy(main.hwnd);
+        session.pump();
+        assert_eq!(session.target(), Some(second));
+        assert_eq!(desktop.owner(panel.hwnd), second.hwnd);
+        assert!(desktop.is_visible(panel.hwnd));
+
+        desktop.destroy(second.hwnd);
+        session.pump();
+        assert_eq!(session.target(), None);
+        assert!(!desktop.is_visible(panel.hwnd));
+    }
+
+    #[test]
+    fn test_auto_attach_errors() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let main = desktop.create(wechat("WeChatMainWndForPC"));
+        let panel = desktop.create(FakeWindow::new("Panel", "panel", Rect::new(0, 0, 200, 300)));
+        let selector = WindowSelector::new().with_class("WeChatMainWndForPC");
+
+        // the companion is gone, the target there already can't be attached to
+        desktop.destroy(panel.hwnd);
+        assert!(AutoAttach::with_system(selector.clone(), panel, desktop.clone()).bind().is_err());
+
+        // from the hook, the callback gets it
+        desktop.destroy(main.hwnd);
+        let errors = Arc::new(Mutex::new(vec![]));
+        let _errors = errors.clone();
+        let session = AutoAttach::with_system(selector, panel, desktop.clone())
+            .on_error(move |w, _| _errors.lock().unwrap().push(w))
+            .bind()
+            .unwrap();
+
+        let again = desktop.create(wechat("WeChatMainWndForPC"));
+        session.pump();
+        assert_eq!(session.target(), None);
+        // tried again on show
+        assert_eq!(*errors.lock().unwrap(), vec![again, again]);
+    }
+
+    #[test]
+    fn test_auto_attach_title_set_later() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let panel = desktop.create(FakeWindow::new("Panel", "panel", Rect::new(0, 0, 200, 300)));
+
+        let session = AutoAttach::with_system(WindowSelector::new().with_title("微信"), panel, desktop.clone())
+            .bind()
+            .unwrap();
+
+        // created hidden and untitled, titled before shown
+        let main = desktop.create(FakeWindow { visible: false, ..FakeWindow::new("WeChatMainWndForPC", "", Rect::new(100, 100, 400, 400)) });
+        session.pump();
+        assert_eq!(session.target(), None);
+
+        desktop.set_title(main.hwnd, "微信");
+        desktop.set_visible(main.hwnd, true);
+        session.pump();
+        assert_eq!(session.target(), Some(main));
+    }
+}
@@ -1,4 +1,8 @@
 pub mod webview2_setup;
 pub mod splash;
 pub mod win2;
-pub mod window_attach;
\ No newline at end of file
+pub mod window_attach;
+pub mod auto_attach;
+pub mod attach_profile;
+pub mod window_snap;
+pub mod window_drag;
\ No newline at end of file
@@ -0,0 +1,221 @@
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::{Receiver, Sender};
+use lazy_static::lazy_static;
+
+use bindings::Windows::Win32::Foundation::HWND;
+
+use super::dpi::ScaleFactor;
+use super::error::Result;
+use super::message_loop::MessageLoop;
+use super::monitor::{enum_monitors, Monitor};
+use super::rect::Rect;
+use super::window::Window;
+use super::window_state::WindowState;
+use super::window_enum::enum_windows;
+use super::window_event::{self, HookScope, WinEvent};
+
+pub type EventChannel = Arc<Mutex<(Sender<WinEvent>, Receiver<WinEvent>)>>;
+
+// the platform backend, everything the attach and event logic
+// needs from the desktop goes through this trait.
+//
+// `Win32System` talks to the real desktop, `FakeDesktop` is a
+// scripted in-memory one for tests.
+pub trait WindowSystem: Send + Sync {
+
+    fn is_window(&self, hwnd: HWND) -> bool;
+
+    // process id and thread id of the window
+    fn pid_tid(&self, hwnd: HWND) -> (u32, u32);
+
+    fn title(&self, hwnd: HWND) -> Option<String>;
+
+    fn class(&self, hwnd: HWND) -> Result<String>;
+
+    fn exe_path(&self, hwnd: HWND) -> Result<String>;
+
+    fn rect(&self, hwnd: HWND) -> Result<Rect>;
+
+    fn set_rect(&self, hwnd: HWND, rect: &Rect, top: bool) -> Result<()>;
+
+    // move without becoming topmost, right above `above` in z order
+    fn set_rect_above(&self, hwnd: HWND, rect: &Rect, above: HWND) -> Result<()>;
+
+    // client area in screen coordinates
+    fn client_rect(&self, hwnd: HWND) -> Result<Rect>;
+
+    // scale of the monitor the window is on
+    fn scale_factor(&self, hwnd: HWND) -> ScaleFactor;
+
+    fn is_visible(&self, hwnd: HWND) -> bool;
+
+    fn set_visible(&self, hwnd: HWND, visible: bool);
+
+    fn is_minimized(&self, hwnd: HWND) -> bool;
+
+    fn is_maximized(&self, hwnd: HWND) -> bool;
+
+    // hidden by dwm, e.g. on another virtual desktop
+    fn is_cloaked(&self, hwnd: HWND) -> bool;
+
+    // mouse input goes to the windows below
+    fn set_click_through(&self, hwnd: HWND, enable: bool) -> Result<()>;
+
+    fn owner(&self, hwnd: HWND) -> HWND;
+
+    fn set_owner(&self, child: HWND, owner: HWND) -> Result<()>;
+
+    // not the child of another window
+    fn is_top_level(&self, hwnd: HWND) -> bool;
+
+    // all monitors of the desktop
+    fn monitors(&self) -> Vec<Monitor>;
+
+    // enum top level windows, stop when callback returns false
+    fn enum_windows(&self, f: &mut dyn FnMut(Window) -> bool) -> bool;
+
+    // install a event hook which sends the events in scope to the
+    // channel, returns the hook id
+    fn install_hook(&self, ch: EventChannel, scope: HookScope) -> Result<isize>;
+
+    fn remove_hook(&self, hook: isize);
+
+    // run the message loop until the callback returns false
+    fn run_loop(&self, cb: &mut dyn FnMut() -> bool);
+
+    fn window_state(&self, hwnd: HWND) -> WindowState {
+        if !self.is_window(hwnd) {
+            return WindowState::Gone;
+        }
+        WindowState::from_flags(
+            true,
+            self.is_visible(hwnd),
+            self.is_minimized(hwnd),
+            self.is_cloaked(hwnd),
+            self.is_maximized(hwnd),
+        )
+    }
+
+    // create a window object with process info from backend
+    fn window(&self, hwnd: HWND) -> Window {
+        let (pid, tid) = self.pid_tid(hwnd);
+        Window::new(hwnd, pid, tid)
+    }
+}
+
+lazy_static! {
+    static ref WIN32_SYSTEM: Arc<dyn WindowSystem> = Arc::new(Win32System);
+}
+
+// the real desktop
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Win32System;
+
+impl Win32System {
+    // the same instance every time, so all listeners share one hook thread
+    pub fn shared() -> Arc<dyn WindowSystem> {
+        WIN32_SYSTEM.clone()
+    }
+}
+
+impl WindowSystem for Win32System {
+
+    fn is_window(&self, hwnd: HWND) -> bool {
+        Window::is_valide_window(hwnd)
+    }
+
+    fn pid_tid(&self, hwnd: HWND) -> (u32, u32) {
+        Window::get_pid_tid(hwnd)
+    }
+
+    fn title(&self, hwnd: HWND) -> Option<String> {
+        Window::from(hwnd).title()
+    }
+
+    fn class(&self, hwnd: HWND) -> Result<String> {
+        Window::from(hwnd).class()
+    }
+
+    fn exe_path(&self, hwnd: HWND) -> Result<String> {
+        Window::from(hwnd).exe_path()
+    }
+
+    fn rect(&self, hwnd: HWND) -> Result<Rect> {
+        Window::get_window_rect(hwnd)
+    }
+
+    fn set_rect(&self, hwnd: HWND, rect: &Rect, top: bool) -> Result<()> {
+        Window::from(hwnd).set_rect(rect, top);
+        Ok(())
+    }
+
+    fn set_rect_above(&self, hwnd: HWND, rect: &Rect, above: HWND) -> Result<()> {
+        Window::set_window_rect_above(hwnd, rect, above)
+    }
+
+    fn client_rect(&self, hwnd: HWND) -> Result<Rect> {
+        Window::get_client_rect(hwnd)
+    }
+
+    fn scale_factor(&self, hwnd: HWND) -> ScaleFactor {
+        ScaleFactor::from_dpi(Window::get_window_dpi(hwnd))
+    }
+
+    fn is_visible(&self, hwnd: HWND) -> bool {
+        Window::is_window_visible(hwnd)
+    }
+
+    fn set_visible(&self, hwnd: HWND, visible: bool) {
+        let w = Window::from(hwnd);
+        if visible { w.show() } else { w.hidden() }
+    }
+
+    fn is_minimized(&self, hwnd: HWND) -> bool {
+        Window::is_iconic(hwnd)
+    }
+
+    fn is_maximized(&self, hwnd: HWND) -> bool {
+        Window::is_zoomed(hwnd)
+    }
+
+    fn is_cloaked(&self, hwnd: HWND) -> bool {
+        Window::is_window_cloaked(hwnd).unwrap_or(false)
+    }
+
+    fn set_click_through(&self, hwnd: HWND, enable: bool) -> Result<()> {
+        Window::set_window_click_through(hwnd, enable)
+    }
+
+    fn owner(&self, hwnd: HWND) -> HWND {
+        Window::get_window_owner(hwnd)
+    }
+
+    fn set_owner(&self, child: HWND, owner: HWND) -> Result<()> {
+        Window::set_window_owner(child, owner)
+    }
+
+    fn is_top_level(&self, hwnd: HWND) -> bool {
+        Window::is_top_level_window(hwnd)
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        enum_monitors()
+    }
+
+    fn enum_windows(&self, f: &mut dyn FnMut(Window) -> bool) -> bool {
+        enum_windows(f)
+    }
+
+    fn install_hook(&self, ch: EventChannel, scope: HookScope) -> Result<isize> {
+        window_event::set_win_event_hook(ch, scope)
+    }
+
+    fn remove_hook(&self, hook: isize) {
+        window_event::unhook_win_event(hook);
+    }
+
+    fn run_loop(&self, cb: &mut dyn FnMut() -> bool) {
+        MessageLoop::start(10, |_msg| cb());
+    }
+}
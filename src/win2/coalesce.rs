@@ -0,0 +1,211 @@
+// coalesce bursts of requests to at most one run per interval,
+// the last request of a burst always runs (trailing edge).
+//
+// Time is passed in from a `Clock`, so the scheduling can be tested
+// with a `ManualClock` without sleeping.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub trait Clock: Send + Sync {
+    // monotonic time since the clock was created
+    fn now(&self) -> Duration;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// clock only moved by hand, for tests
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, d: Duration) {
+        *self.now.lock().unwrap() += d;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Coalescer {
+    interval: Duration,
+    last_run: Option<Duration>,
+    pending: bool,
+}
+
+impl Coalescer {
+    pub fn new(interval: Duration) -> Self {
+        Coalescer {
+            interval,
+            last_run: None,
+            pending: false,
+        }
+    }
+
+    fn due(&self, now: Duration) -> bool {
+        match self.last_run {
+            Some(last) => now >= last + self.interval,
+            None => true,
+        }
+    }
+
+    // a new request, true when it should run now,
+    // otherwise it is kept until `poll` says it is due.
+    pub fn request(&mut self, now: Duration) -> bool {
+        if self.due(now) {
+            self.last_run = Some(now);
+            self.pending = false;
+            return true;
+        }
+
+        self.pending = true;
+        false
+    }
+
+    // true when a kept request should run now
+    pub fn poll(&mut self, now: Duration) -> bool {
+        if self.pending && self.due(now) {
+            self.last_run = Some(now);
+            self.pending = false;
+            return true;
+        }
+
+        false
+    }
+
+    // run now whatever is kept, e.g. at the end of a drag.
+    // true when there was a kept request.
+    pub fn flush(&mut self, now: Duration) -> bool {
+        let pending = self.pending;
+        self.last_run = Some(now);
+        self.pending = false;
+        pending
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    // when the kept request will be due
+    pub fn next_deadline(&self) -> Option<Duration> {
+        if !self.pending {
+            return None;
+        }
+        Some(self.last_run.map(|last| last + self.interval).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Clock, Coalescer, ManualClock};
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn test_first_request_runs() {
+        let mut c = Coalescer::new(ms(16));
+        assert!(c.request(ms(0)));
+        assert!(!c.is_pending());
+        assert!(!c.poll(ms(100)));
+    }
+
+    #[test]
+    fn test_burst_runs_once_per_interval() {
+        let clock = ManualClock::new();
+        let mut c = Coalescer::new(ms(16));
+        let mut runs = vec![];
+
+        // 100 requests, one every 2ms, polled in between
+        for _ in 0..100 {
+            if c.request(clock.now()) {
+                runs.push(clock.now());
+            }
+            clock.advance(ms(1));
+            if c.poll(clock.now()) {
+                runs.push(clock.now());
+            }
+            clock.advance(ms(1));
+        }
+
+        // trailing run after the burst
+        clock.advance(ms(16));
+        if c.poll(clock.now()) {
+            runs.push(clock.now());
+        }
+
+        assert!(!c.is_pending());
+        assert_eq!(runs.first(), Some(&ms(0)));
+        // every 16ms from 0 to 192, then the trailing one
+        assert_eq!(runs.len(), 14);
+        assert_eq!(runs.last(), Some(&ms(216)));
+        for pair in runs.windows(2) {
+            assert!(pair[1] - pair[0] >= ms(16));
+        }
+    }
+
+    #[test]
+    fn test_trailing_edge() {
+        let mut c = Coalescer::new(ms(16));
+        assert!(c.request(ms(0)));
+        assert!(!c.request(ms(5)));
+        assert_eq!(c.next_deadline(), Some(ms(16)));
+
+        assert!(!c.poll(ms(15)));
+        assert!(c.poll(ms(16)));
+        assert_eq!(c.next_deadline(), None);
+        assert!(!c.poll(ms(40)));
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut c = Coalescer::new(ms(16));
+        assert!(c.request(ms(0)));
+        assert!(!c.flush(ms(1)));
+
+        assert!(!c.request(ms(2)));
+        assert!(c.flush(ms(3)));
+        assert!(!c.is_pending());
+
+        // the flush counts as a run
+        assert!(!c.request(ms(10)));
+        assert!(c.request(ms(40)));
+    }
+
+    #[test]
+    fn test_zero_interval_never_keeps() {
+        let mut c = Coalescer::new(ms(0));
+        for i in 0..10 {
+            assert!(c.request(ms(i)));
+            assert!(c.request(ms(i)));
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// logical and physical coordinates.
+//
+// Win32 gives us physical pixels, while sizes we configure are in
+// logical units (pixels at 100% scale, 96 dpi). The scale factor comes
+// from the monitor of the window, e.g. 1.5 for 150%.
+
+use super::point::{Offset, Point, Size};
+use super::rect::Rect;
+
+pub const BASE_DPI: u32 = 96;
+
+/// Physical pixels per logical unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleFactor(pub f64);
+
+impl Default for ScaleFactor {
+    fn default() -> Self {
+        ScaleFactor(1.0)
+    }
+}
+
+impl ScaleFactor {
+    pub fn from_dpi(dpi: u32) -> Self {
+        if dpi == 0 {
+            return Self::default();
+        }
+        ScaleFactor(dpi as f64 / BASE_DPI as f64)
+    }
+
+    pub fn dpi(&self) -> u32 {
+        (self.0 * BASE_DPI as f64).round() as u32
+    }
+
+    pub fn to_physical(&self, v: i32) -> i32 {
+        (v as f64 * self.0).round() as i32
+    }
+
+    pub fn to_logical(&self, v: i32) -> i32 {
+        (v as f64 / self.0).round() as i32
+    }
+}
+
+/// Rect in logical units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LogicalRect(pub Rect);
+
+/// Rect in physical pixels, what Win32 uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PhysicalRect(pub Rect);
+
+// edges are scaled instead of x and width, so rects sharing an
+// edge still share it after the conversion.
+fn scale_rect(rect: Rect, f: impl Fn(i32) -> i32) -> Rect {
+    Rect::from_ltrb(f(rect.left()), f(rect.top()), f(rect.right()), f(rect.bottom()))
+}
+
+impl LogicalRect {
+    pub fn to_physical(&self, scale: ScaleFactor) -> PhysicalRect {
+        PhysicalRect(scale_rect(self.0, |v| scale.to_physical(v)))
+    }
+}
+
+impl PhysicalRect {
+    pub fn to_logical(&self, scale: ScaleFactor) -> LogicalRect {
+        LogicalRect(scale_rect(self.0, |v| scale.to_logical(v)))
+    }
+}
+
+impl ScaleFactor {
+    pub fn point_to_physical(&self, p: Point) -> Point {
+        Point::new(self.to_physical(p.x), self.to_physical(p.y))
+    }
+
+    pub fn point_to_logical(&self, p: Point) -> Point {
+        Point::new(self.to_logical(p.x), self.to_logical(p.y))
+    }
+
+    pub fn size_to_physical(&self, s: Size) -> Size {
+        Size::new(self.to_physical(s.width), self.to_physical(s.height))
+    }
+
+    pub fn size_to_logical(&self, s: Size) -> Size {
+        Size::new(self.to_logical(s.width), self.to_logical(s.height))
+    }
+
+    pub fn offset_to_physical(&self, o: Offset) -> Offset {
+        Offset::new(self.to_physical(o.dx), self.to_physical(o.dy))
+    }
+
+    pub fn offset_to_logical(&self, o: Offset) -> Offset {
+        Offset::new(self.to_logical(o.dx), self.to_logical(o.dy))
+    }
+}
+
+/// Which units values given to the attach builder are in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unit {
+    #[default]
+    Physical,
+    Logical,
+}
+
+impl Unit {
+    // convert a value in this unit to physical pixels
+    pub fn to_physical(&self, v: i32, scale: ScaleFactor) -> i32 {
+        match self {
+            Unit::Physical => v,
+            Unit::Logical => scale.to_physical(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::win2::{point::{Offset, Point, Size}, rect::Rect};
+
+    use super::{LogicalRect, PhysicalRect, ScaleFactor, Unit};
+
+    #[test]
+    fn test_scale_from_dpi() {
+        let cases = [(96, 1.0), (120, 1.25), (144, 1.5), (192, 2.0), (0, 1.0)];
+        for (dpi, scale) in cases.iter() {
+            assert_eq!(ScaleFactor::from_dpi(*dpi).0, *scale);
+        }
+        assert_eq!(ScaleFactor(1.5).dpi(), 144);
+    }
+
+    #[test]
+    fn test_scale_values() {
+        let s = ScaleFactor(1.5);
+        assert_eq!(s.to_physical(100), 150);
+        assert_eq!(s.to_physical(-10), -15);
+        assert_eq!(s.to_logical(150), 100);
+        assert_eq!(s.to_physical(1), 2);
+        assert_eq!(s.point_to_physical(Point::new(10, 20)), Point::new(15, 30));
+        assert_eq!(s.size_to_logical(Size::new(300, 150)), Size::new(200, 100));
+        assert_eq!(s.offset_to_physical(Offset::new(-10, 4)), Offset::new(-15, 6));
+    }
+
+    #[test]
+    fn test_rect_round_trip() {
+        let cases = [
+            (1.0, Rect::new(10, 20, 300, 200), Rect::new(10, 20, 300, 200)),
+            (1.5, Rect::new(10, 20, 300, 200), Rect::new(15, 30, 450, 300)),
+            (2.0, Rect::new(-100, 0, 50, 50), Rect::new(-200, 0, 100, 100)),
+            (1.25, Rect::new(0, 0, 800, 600), Rect::new(0, 0, 1000, 750)),
+        ];
+
+        for (scale, logical, physical) in cases.iter() {
+            let scale = ScaleFactor(*scale);
+            assert_eq!(LogicalRect(*logical).to_physical(scale), PhysicalRect(*physical));
+            assert_eq!(PhysicalRect(*physical).to_logical(scale), LogicalRect(*logical));
+        }
+    }
+
+    #[test]
+    fn test_rect_keeps_shared_edges() {
+        // odd logical values round on each edge, the neighbour
+        // must still start where the first one ends.
+        let scale = ScaleFactor(1.25);
+        let a = LogicalRect(Rect::new(0, 0, 3, 3)).to_physical(scale).0;
+        let b = LogicalRect(Rect::new(3, 0, 3, 3)).to_physical(scale).0;
+        assert_eq!(a.right(), b.left());
+    }
+
+    #[test]
+    fn test_unit() {
+        assert_eq!(Unit::Physical.to_physical(10, ScaleFactor(2.0)), 10);
+        assert_eq!(Unit::Logical.to_physical(10, ScaleFactor(2.0)), 20);
+    }
+}
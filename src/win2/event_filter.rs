@@ -0,0 +1,192 @@
+// predicates on an event and its window, so handlers don't start
+// with the same checks, e.g. the main window of WeChat:
+//
+//     EventFilter::WindowObject & EventFilter::Exe("WeChat.exe".into()) & EventFilter::class_regex("^WeChatMainWnd")?
+
+use std::io::{Error, ErrorKind};
+use std::ops::{BitAnd, BitOr, Not};
+
+use regex::Regex;
+
+use super::backend::WindowSystem;
+use super::error::Result;
+use super::selector::{exe_matches, WindowSelector};
+use super::window_event::WinEvent;
+
+// id of the window itself, other ids are its caret, scrollbars etc.
+const OBJID_WINDOW: i32 = 0;
+
+#[derive(Debug, Clone)]
+pub enum EventFilter {
+    // about the window, not a part of it
+    WindowObject,
+    Class(String),
+    ClassMatches(Regex),
+    TitleMatches(Regex),
+    // file name like `WeChat.exe` or a full path, case insensitive
+    Exe(String),
+    Pid(u32),
+    Visible,
+    TopLevel,
+    Selector(WindowSelector),
+
+    // empty is true
+    All(Vec<EventFilter>),
+    // empty is false
+    Any(Vec<EventFilter>),
+    Not(Box<EventFilter>),
+}
+
+impl EventFilter {
+    pub fn class_regex(pattern: &str) -> Result<Self> {
+        Ok(Self::ClassMatches(regex(pattern)?))
+    }
+
+    pub fn title_regex(pattern: &str) -> Result<Self> {
+        Ok(Self::TitleMatches(regex(pattern)?))
+    }
+
+    pub fn and(self, other: EventFilter) -> Self {
+        match self {
+            Self::All(mut v) => {
+                v.push(other);
+                Self::All(v)
+            },
+            f => Self::All(vec![f, other]),
+        }
+    }
+
+    pub fn or(self, other: EventFilter) -> Self {
+        match self {
+            Self::Any(mut v) => {
+                v.push(other);
+                Self::Any(v)
+            },
+            f => Self::Any(vec![f, other]),
+        }
+    }
+
+    pub fn matches(&self, sys: &dyn WindowSystem, evt: &WinEvent) -> bool {
+        let hwnd = evt.window.hwnd;
+        match self {
+            Self::WindowObject => evt.raw_id_object == OBJID_WINDOW,
+            Self::Class(class) => sys.class(hwnd).ok().as_ref() == Some(class),
+            Self::ClassMatches(re) => sys.class(hwnd).is_ok_and(|class| re.is_match(&class)),
+            Self::TitleMatches(re) => sys.title(hwnd).is_some_and(|title| re.is_match(&title)),
+            Self::Exe(exe) => sys.exe_path(hwnd).is_ok_and(|path| exe_matches(exe, &path)),
+            Self::Pid(pid) => evt.window.pid == *pid,
+            Self::Visible => sys.is_visible(hwnd),
+            Self::TopLevel => sys.is_top_level(hwnd),
+            Self::Selector(selector) => selector.matches(sys, hwnd),
+
+            Self::All(v) => v.iter().all(|f| f.matches(sys, evt)),
+            Self::Any(v) => v.iter().any(|f| f.matches(sys, evt)),
+            Self::Not(f) => !f.matches(sys, evt),
+        }
+    }
+}
+
+fn regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+}
+
+impl BitAnd for EventFilter {
+    type Output = EventFilter;
+
+    fn bitand(self, other: EventFilter) -> EventFilter {
+        self.and(other)
+    }
+}
+
+impl BitOr for EventFilter {
+    type Output = EventFilter;
+
+    fn bitor(self, other: EventFilter) -> EventFilter {
+        self.or(other)
+    }
+}
+
+impl Not for EventFilter {
+    type Output = EventFilter;
+
+    fn not(self) -> EventFilter {
+        match self {
+            Self::Not(f) => *f,
+            f => Self::Not(Box::new(f)),
+        }
+    }
+}
+
+impl From<WindowSelector> for EventFilter {
+    fn from(selector: WindowSelector) -> Self {
+        Self::Selector(selector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bindings::Windows::Win32::Foundation::HWND;
+    use bindings::Windows::Win32::UI::WindowsAndMessaging::EVENT_OBJECT_SHOW;
+
+    use crate::win2::{fake::{FakeDesktop, FakeWindow}, rect::Rect, selector::WindowSelector, window::Window, window_event::WinEvent};
+
+    use super::EventFilter;
+
+    #[test]
+    fn test_event_filter() {
+        let desktop = FakeDesktop::new();
+        let login = desktop.create(FakeWindow {
+            pid: 7,
+            exe_path: "C:\\Program Files\\Tencent\\WeChat\\WeChat.exe".to_string(),
+            ..FakeWindow::new("WeChatLoginWndForPC", "微信", Rect::new(0, 0, 100, 100))
+        });
+        let main = desktop.create(FakeWindow {
+            pid: 7,
+            visible: false,
+            exe_path: "C:\\Program Files\\Tencent\\WeChat\\WeChat.exe".to_string(),
+            ..FakeWindow::new("WeChatMainWndForPC", "微信", Rect::new(0, 0, 100, 100))
+        });
+        let edit = desktop.create(FakeWindow {
+            parent: main.hwnd,
+            exe_path: "C:\\Windows\\notepad.exe".to_string(),
+            ..FakeWindow::new("Edit", "", Rect::new(0, 0, 100, 100))
+        });
+
+        let evt = |w, object| {
+            let mut evt = WinEvent::with_window(Default::default(), EVENT_OBJECT_SHOW, w);
+            evt.raw_id_object = object;
+            evt
+        };
+        let events = [evt(login, 0), evt(main, 0), evt(edit, 0), evt(login, -8)];
+
+        use EventFilter::*;
+        let wechat = Exe("wechat.exe".to_string());
+        let cases = [
+            (WindowObject, [true, true, true, false]),
+            (Class("Edit".to_string()), [false, false, true, false]),
+            (EventFilter::class_regex("^WeChat.*ForPC$").unwrap(), [true, true, false, true]),
+            (EventFilter::title_regex("微").unwrap(), [true, true, false, true]),
+            (wechat.clone(), [true, true, false, true]),
+            (Pid(7), [true, true, false, true]),
+            (Visible, [true, false, true, true]),
+            (TopLevel, [true, true, false, true]),
+            (WindowSelector::new().with_class("WeChatMainWndForPC").into(), [false, true, false, false]),
+            (WindowObject & wechat.clone() & Visible, [true, false, false, false]),
+            (!wechat.clone() | Pid(7), [true, true, true, true]),
+            (!(WindowObject & TopLevel), [false, false, true, true]),
+            (!!Visible, [true, false, true, true]),
+            (All(vec![]), [true; 4]),
+            (Any(vec![]), [false; 4]),
+        ];
+
+        for (filter, expected) in cases.iter() {
+            let got = events.iter().map(|e| filter.matches(&desktop, e)).collect::<Vec<_>>();
+            assert_eq!(got, expected.to_vec(), "{:?}", filter);
+        }
+
+        // gone windows match nothing about them
+        let gone = evt(Window { hwnd: HWND(99), pid: 0, tid: 0 }, 0);
+        assert!(!Visible.matches(&desktop, &gone) && !TopLevel.matches(&desktop, &gone));
+        assert!(EventFilter::title_regex("(").is_err());
+    }
+}
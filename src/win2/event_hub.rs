@@ -0,0 +1,460 @@
+// one hook thread for the whole process.
+//
+// The hooks are installed by the thread of the hub and their
+// callbacks run there, each event is routed to the channels of the
+// subscribers it is of interest to. Listeners started in the
+// background are run by that thread too, none needs its own.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, ThreadId};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use lazy_static::lazy_static;
+
+use bindings::Windows::Win32::Foundation::HWND;
+
+use super::backend::{EventChannel, WindowSystem};
+use super::error::Result;
+use super::window::Window;
+use super::window_event::{event_ranges, missing_ranges, HookScope, WinEvent, WinEventType};
+
+lazy_static! {
+    // one hub per backend, e.g. each fake desktop has its own
+    static ref HUBS: Mutex<Vec<(usize, Weak<EventHub>)>> = Mutex::new(vec![]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriberId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RunId(u64);
+
+// the events a subscriber gets
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Interest {
+    // none when empty, `All` is any
+    pub types: Vec<WinEventType>,
+    // only the events of this window
+    pub window: Option<HWND>,
+    // process and thread the hooks are scoped to, 0 is any
+    pub pid: u32,
+    pub tid: u32,
+}
+
+impl Interest {
+    pub fn new(types: Vec<WinEventType>) -> Self {
+        Self { types, ..Default::default() }
+    }
+
+    // the events of a window, of its process and thread, or of all
+    // windows when it's not one, e.g. `Window::default()`
+    pub fn of_window(sys: &dyn WindowSystem, w: Window, types: Vec<WinEventType>) -> Self {
+        if !sys.is_window(w.hwnd) {
+            return Self::new(types);
+        }
+
+        let (pid, tid) = sys.pid_tid(w.hwnd);
+        Self { types, window: Some(w.hwnd), pid, tid }
+    }
+
+    pub fn matches(&self, evt: &WinEvent) -> bool {
+        let typed = self.types.iter().any(|t| t.covers(evt.etype));
+        typed && match self.window {
+            Some(hwnd) => hwnd == evt.window.hwnd,
+            None => true,
+        }
+    }
+}
+
+// gets the events on the thread routing them, must not block
+pub type Sink = Box<dyn FnMut(&WinEvent) + Send + 'static>;
+
+struct Subscriber {
+    id: SubscriberId,
+    interest: Interest,
+    sink: Sink,
+}
+
+type Run = Box<dyn FnMut() + Send + 'static>;
+
+enum Command {
+    // install and remove hooks for the subscribers, on the hub thread
+    Sync(Sender<Result<()>>),
+}
+
+pub struct EventHub {
+    sys: Arc<dyn WindowSystem>,
+
+    // all hooks send here
+    ch: EventChannel,
+    subscribers: Mutex<Vec<Subscriber>>,
+    // hook ids and what they get, shared with the thread to
+    // unhook what's left when it ends
+    hooks: Arc<Mutex<Vec<(HookScope, isize)>>>,
+
+    // run by the thread after each round of events, taken
+    // out while running, those stopped meanwhile are noted
+    runs: Mutex<Vec<(RunId, Run)>>,
+    stopped: Mutex<Vec<RunId>>,
+
+    commands: Sender<Command>,
+    thread: ThreadId,
+    next_id: AtomicU64,
+}
+
+impl EventHub {
+
+    // the hub of the backend, started with the first subscriber
+    // and gone with the last one
+    pub fn for_system(sys: &Arc<dyn WindowSystem>) -> Arc<EventHub> {
+        let key = Arc::as_ptr(sys) as *const () as usize;
+
+        let mut hubs = HUBS.lock().unwrap();
+        hubs.retain(|(_, hub)| hub.strong_count() > 0);
+        if let Some(hub) = hubs.iter().find(|(k, _)| *k == key).and_then(|(_, hub)| hub.upgrade()) {
+            return hub;
+        }
+
+        let hub = Self::start(sys.clone());
+        hubs.push((key, Arc::downgrade(&hub)));
+        hub
+    }
+
+    fn start(sys: Arc<dyn WindowSystem>) -> Arc<EventHub> {
+        let (commands, command_rx) = unbounded();
+        let (hub_tx, hub_rx) = crossbeam_channel::bounded::<Weak<EventHub>>(1);
+
+        let hooks = Arc::new(Mutex::new(vec![]));
+        let (_sys, _hooks) = (sys.clone(), hooks.clone());
+
+        let handle = thread::spawn(move || {
+            let weak = hub_rx.recv().unwrap_or_default();
+            _sys.run_loop(&mut || match weak.upgrade() {
+                Some(hub) => {
+                    hub.tick(&command_rx);
+                    true
+                },
+                None => false,
+            });
+
+            // gone, unhook here as win32 only lets the thread that
+            // hooked unhook, the dropping one may be any
+            for (_, hook) in _hooks.lock().unwrap().drain(..) {
+                _sys.remove_hook(hook);
+            }
+        });
+
+        let hub = Arc::new(EventHub {
+            sys,
+            ch: Arc::new(Mutex::new(unbounded())),
+            subscribers: Mutex::new(vec![]),
+            hooks,
+            runs: Mutex::new(vec![]),
+            stopped: Mutex::new(vec![]),
+            commands,
+            thread: handle.thread().id(),
+            next_id: AtomicU64::new(1),
+        });
+        let _ = hub_tx.send(Arc::downgrade(&hub));
+
+        hub
+    }
+
+    // the events of interest are sent to `ch` from now on
+    pub fn subscribe(&self, interest: Interest, ch: Sender<WinEvent>) -> Result<SubscriberId> {
+        self.subscribe_fn(interest, Box::new(move |evt: &WinEvent| {
+            let _ = ch.send(*evt);
+        }))
+    }
+
+    // like `subscribe`, the events are handed to `sink`
+    pub fn subscribe_fn(&self, interest: Interest, sink: Sink) -> Result<SubscriberId> {
+        let id = SubscriberId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.subscribers.lock().unwrap().push(Subscriber { id, interest, sink });
+
+        if let Err(e) = self.sync() {
+            self.unsubscribe(id);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    // change what the subscriber gets, e.g. a handler was added
+    pub fn update(&self, id: SubscriberId, interest: Interest) -> Result<()> {
+        let changed = match self.subscribers.lock().unwrap().iter_mut().find(|s| s.id == id) {
+            Some(s) if s.interest != interest => {
+                s.interest = interest;
+                true
+            },
+            _ => false,
+        };
+
+        if changed { self.sync() } else { Ok(()) }
+    }
+
+    pub fn unsubscribe(&self, id: SubscriberId) {
+        self.subscribers.lock().unwrap().retain(|s| s.id != id);
+        let _ = self.sync();
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    // call `run` on the hub thread after each round of events
+    pub fn run<F>(&self, run: F) -> RunId
+    where
+        F: FnMut() + Send + 'static
+    {
+        let id = RunId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.runs.lock().unwrap().push((id, Box::new(run)));
+        id
+    }
+
+    pub fn stop(&self, id: RunId) {
+        let mut runs = self.runs.lock().unwrap();
+        let count = runs.len();
+        runs.retain(|(i, _)| *i != id);
+
+        // running right now
+        if runs.len() == count {
+            self.stopped.lock().unwrap().push(id);
+        }
+    }
+
+    // route the queued events to the subscribers, returns how many
+    // were taken. The hub thread does it all the time, pumping
+    // listeners do it first to not wait for it.
+    pub fn dispatch(&self) -> usize {
+        // held while routing, keeps the order
+        let ch = self.ch.lock().unwrap();
+        let mut count = 0;
+
+        while let Ok(evt) = ch.1.try_recv() {
+            count += 1;
+
+            // process and thread of the hook it came by
+            let scope = match self.hooks.lock().unwrap().iter().find(|(_, h)| *h == evt.hook_handle.0) {
+                Some((scope, _)) => *scope,
+                None => continue,
+            };
+
+            for s in self.subscribers.lock().unwrap().iter_mut() {
+                if (s.interest.pid, s.interest.tid) == (scope.pid, scope.tid) && s.interest.matches(&evt) {
+                    (s.sink)(&evt);
+                }
+            }
+        }
+
+        count
+    }
+
+    fn tick(&self, commands: &Receiver<Command>) {
+        while let Ok(cmd) = commands.try_recv() {
+            match cmd {
+                Command::Sync(ack) => {
+                    let _ = ack.send(self.sync_hooks());
+                },
+            }
+        }
+
+        self.dispatch();
+
+        // runs may start and stop others, don't hold the lock
+        let mut runs = std::mem::take(&mut *self.runs.lock().unwrap());
+        for (_, run) in runs.iter_mut() {
+            run();
+        }
+
+        let mut current = self.runs.lock().unwrap();
+        let stopped = std::mem::take(&mut *self.stopped.lock().unwrap());
+        runs.retain(|(id, _)| !stopped.contains(id));
+        runs.append(&mut current);
+        *current = runs;
+    }
+
+    // hooks are made on the hub thread, wait for it
+    fn sync(&self) -> Result<()> {
+        if thread::current().id() == self.thread {
+            return self.sync_hooks();
+        }
+
+        let (ack, done) = unbounded();
+        if self.commands.send(Command::Sync(ack)).is_err() {
+            return self.sync_hooks();
+        }
+        done.recv().unwrap_or_else(|_| self.sync_hooks())
+    }
+
+    // the hooks the subscribers need, no more
+    fn sync_hooks(&self) -> Result<()> {
+        let mut groups: Vec<((u32, u32), Vec<WinEventType>)> = vec![];
+        for s in self.subscribers.lock().unwrap().iter() {
+            let key = (s.interest.pid, s.interest.tid);
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, types)) => types.extend_from_slice(&s.interest.types),
+                None => groups.push((key, s.interest.types.clone())),
+            }
+        }
+        let needed = groups.into_iter()
+            .map(|(key, types)| (key, event_ranges(&types)))
+            .collect::<Vec<_>>();
+
+        let mut hooks = self.hooks.lock().unwrap();
+        let sys = &self.sys;
+        // a wider hook than needed goes too, e.g. of the last `All`
+        hooks.retain(|(scope, hook)| {
+            let used = needed.iter().any(|((pid, tid), ranges)| {
+                (*pid, *tid) == (scope.pid, scope.tid) && ranges.contains(&(scope.min, scope.max))
+            });
+            if !used {
+                sys.remove_hook(*hook);
+            }
+            used
+        });
+
+        for ((pid, tid), ranges) in needed {
+            let have = hooks.iter()
+                .filter(|(scope, _)| (scope.pid, scope.tid) == (pid, tid))
+                .map(|(scope, _)| (scope.min, scope.max))
+                .collect::<Vec<_>>();
+
+            for (min, max) in missing_ranges(&ranges, &have) {
+                let scope = HookScope { min, max, pid, tid };
+                let hook = sys.install_hook(self.ch.clone(), scope)?;
+                hooks.push((scope, hook));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bindings::Windows::Win32::UI::WindowsAndMessaging::{EVENT_MAX, EVENT_MIN, EVENT_OBJECT_LOCATIONCHANGE};
+    use crossbeam_channel::unbounded;
+
+    use crate::win2::{backend::WindowSystem, fake::{FakeDesktop, FakeWindow}, rect::Rect, window::Window};
+    use crate::win2::window_event::{HookScope, WinEvent, WinEventListener, WinEventType};
+
+    use super::{EventHub, Interest};
+
+    #[test]
+    fn test_hub_routes_by_interest() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let a = desktop.create(FakeWindow { pid: 7, tid: 7, ..FakeWindow::new("A", "a", Rect::new(0, 0, 100, 100)) });
+        let b = desktop.create(FakeWindow { pid: 9, tid: 9, ..FakeWindow::new("B", "b", Rect::new(0, 0, 100, 100)) });
+
+        let sys: Arc<dyn WindowSystem> = desktop.clone();
+        let hub = EventHub::for_system(&sys);
+        assert!(Arc::ptr_eq(&hub, &EventHub::for_system(&sys)));
+
+        let (moves_tx, moves) = unbounded();
+        let (a_tx, of_a) = unbounded();
+        let (shows_tx, shows) = unbounded();
+
+        hub.subscribe(Interest::new(vec![WinEventType::LocationChange]), moves_tx).unwrap();
+        let a_id = hub.subscribe(Interest { window: Some(a.hwnd), pid: 7, tid: 7, ..Interest::new(vec![WinEventType::All]) }, a_tx).unwrap();
+        let shows_id = hub.subscribe(Interest::new(vec![WinEventType::Show]), shows_tx.clone()).unwrap();
+
+        // the same interest twice hooks nothing more
+        let hooks = desktop.hooks().len();
+        let again = hub.subscribe(Interest::new(vec![WinEventType::Show]), shows_tx).unwrap();
+        assert_eq!(desktop.hooks().len(), hooks);
+
+        desktop.move_to(a.hwnd, Rect::new(1, 1, 100, 100));
+        desktop.move_to(b.hwnd, Rect::new(2, 2, 100, 100));
+        desktop.set_visible(b.hwnd, false);
+        desktop.set_visible(b.hwnd, true);
+        hub.dispatch();
+
+        let windows = |rx: &crossbeam_channel::Receiver<WinEvent>| rx.try_iter().map(|e| (e.etype, e.window)).collect::<Vec<_>>();
+        assert_eq!(windows(&moves), vec![(WinEventType::LocationChange, a), (WinEventType::LocationChange, b)]);
+        assert_eq!(windows(&of_a), vec![(WinEventType::LocationChange, a)]);
+        // once per subscription
+        assert_eq!(windows(&shows), vec![(WinEventType::Shown, b), (WinEventType::Shown, b)]);
+
+        // hooks go with the last one needing them
+        hub.unsubscribe(again);
+        hub.unsubscribe(shows_id);
+        hub.unsubscribe(a_id);
+        assert_eq!(desktop.hooks().len(), 1);
+        assert_eq!(hub.subscribers(), 1);
+
+        // the thread unhooks the rest when the hub is dropped
+        drop(hub);
+        for _ in 0..500 {
+            if desktop.hooks().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(desktop.hooks(), vec![]);
+    }
+
+    #[test]
+    fn test_hub_narrows_hooks() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let sys: Arc<dyn WindowSystem> = desktop.clone();
+        let hub = EventHub::for_system(&sys);
+
+        let (tx, _rx) = unbounded();
+        let all = hub.subscribe(Interest::new(vec![WinEventType::All]), tx.clone()).unwrap();
+        hub.subscribe(Interest::new(vec![WinEventType::LocationChange]), tx).unwrap();
+        assert_eq!(desktop.hooks(), vec![HookScope { min: EVENT_MIN, max: EVENT_MAX, pid: 0, tid: 0 }]);
+
+        // only what the one left needs
+        hub.unsubscribe(all);
+        assert_eq!(desktop.hooks(), vec![
+            HookScope { min: EVENT_OBJECT_LOCATIONCHANGE, max: EVENT_OBJECT_LOCATIONCHANGE, pid: 0, tid: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_listeners_share_hub_thread() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)));
+
+        // a handler on the hub thread starting another listener
+        let started = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (_started, _seen, _desktop) = (started.clone(), seen.clone(), desktop.clone());
+
+        let mut listener = WinEventListener::with_system(Window::default(), desktop.clone());
+        listener.on(WinEventType::Create, move |evt: &WinEvent| {
+            let _seen = _seen.clone();
+            let mut inner = WinEventListener::with_system(evt.window, _desktop.clone());
+            inner.on(WinEventType::LocationChange, move |evt: &WinEvent| _seen.lock().unwrap().push(evt.window));
+            inner.start(false).unwrap();
+            _started.lock().unwrap().push(inner);
+        });
+        listener.start(false).unwrap();
+
+        let other = desktop.create(FakeWindow::new("Other", "other", Rect::new(0, 0, 100, 100)));
+        for _ in 0..500 {
+            if started.lock().unwrap().len() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        desktop.move_to(target.hwnd, Rect::new(5, 5, 100, 100));
+        desktop.move_to(other.hwnd, Rect::new(5, 5, 100, 100));
+        for _ in 0..500 {
+            if !seen.lock().unwrap().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(*seen.lock().unwrap(), vec![other]);
+
+        drop(listener);
+        started.lock().unwrap().clear();
+        assert_eq!(desktop.hooks(), vec![]);
+    }
+}
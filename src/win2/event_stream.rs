@@ -0,0 +1,258 @@
+// window events as values instead of callbacks: an iterator, a
+// crossbeam receiver or an async stream, all fed by the hub.
+//
+//     let mut stream = Events::new(window).of(WinEventType::Show).stream()?;
+//     while let Some(evt) = stream.next().await { .. }
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Receiver};
+use futures_core::Stream;
+
+use super::backend::{Win32System, WindowSystem};
+use super::error::Result;
+use super::event_hub::{EventHub, Interest, SubscriberId};
+use super::window::Window;
+use super::window_event::{WinEvent, WinEventType};
+
+type Filter = Arc<dyn Fn(&WinEvent) -> bool + Send + Sync + 'static>;
+
+// what a stream gets, all events of the window by default
+pub struct Events {
+    w: Window,
+    types: Vec<WinEventType>,
+    filter: Option<Filter>,
+
+    pub(crate) sys: Arc<dyn WindowSystem>,
+}
+
+impl Events {
+    pub fn new(w: Window) -> Self {
+        Self::with_system(w, Win32System::shared())
+    }
+
+    pub fn with_system(w: Window, sys: Arc<dyn WindowSystem>) -> Self {
+        Self { w, types: vec![], filter: None, sys }
+    }
+
+    // only events of the type, may be given more than once
+    pub fn of(&mut self, typ: WinEventType) -> &mut Self {
+        self.types.push(typ);
+        self
+    }
+
+    // drop events before they are queued, each filter given
+    // must pass
+    pub fn filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&WinEvent) -> bool + Send + Sync + 'static
+    {
+        self.filter = Some(match self.filter.take() {
+            Some(prev) => Arc::new(move |evt: &WinEvent| prev(evt) && filter(evt)),
+            None => Arc::new(filter),
+        });
+        self
+    }
+
+    pub fn stream(&mut self) -> Result<EventStream> {
+        let (tx, rx) = unbounded();
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let _waker = waker.clone();
+
+        let (hub, id) = self.subscribe(move |evt: &WinEvent| {
+            let _ = tx.send(*evt);
+            if let Some(waker) = _waker.lock().unwrap().take() {
+                waker.wake();
+            }
+        })?;
+
+        Ok(EventStream { hub, id, rx, waker })
+    }
+
+    // hand the wanted events to `sink` on the thread routing them
+    pub(crate) fn subscribe<F>(&mut self, mut sink: F) -> Result<(Arc<EventHub>, SubscriberId)>
+    where
+        F: FnMut(&WinEvent) + Send + 'static
+    {
+        let types = if self.types.is_empty() { vec![WinEventType::All] } else { self.types.clone() };
+        let interest = Interest::of_window(&*self.sys, self.w, types);
+
+        let filter = self.filter.clone();
+        let hub = EventHub::for_system(&self.sys);
+        let id = hub.subscribe_fn(interest, Box::new(move |evt: &WinEvent| {
+            if filter.as_ref().is_some_and(|f| !f(evt)) {
+                return;
+            }
+            sink(evt);
+        }))?;
+
+        Ok((hub, id))
+    }
+}
+
+impl Window {
+    // all events of the window, in the background
+    pub fn events(&self) -> Result<EventStream> {
+        Events::new(*self).stream()
+    }
+}
+
+// queued events, they stop with the stream being dropped
+pub struct EventStream {
+    hub: Arc<EventHub>,
+    id: SubscriberId,
+    rx: Receiver<WinEvent>,
+    // of the task waiting in `poll_next`
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl EventStream {
+    // e.g. to `select!` it with other channels
+    pub fn receiver(&self) -> &Receiver<WinEvent> {
+        &self.rx
+    }
+
+    // next queued event, without waiting
+    pub fn try_next(&self) -> Option<WinEvent> {
+        // don't wait for the hub thread to route them
+        self.hub.dispatch();
+        self.rx.try_recv().ok()
+    }
+
+    pub fn next_timeout(&self, timeout: Duration) -> Option<WinEvent> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    // blocks until there is an event, not an `Iterator` itself
+    // so `next` is the one of `StreamExt`
+    pub fn iter(&self) -> crossbeam_channel::Iter<'_, WinEvent> {
+        self.rx.iter()
+    }
+}
+
+// `for evt in window.events()? { .. }`
+impl IntoIterator for EventStream {
+    type Item = WinEvent;
+    type IntoIter = EventIter;
+
+    fn into_iter(self) -> EventIter {
+        EventIter(self)
+    }
+}
+
+pub struct EventIter(EventStream);
+
+impl Iterator for EventIter {
+    type Item = WinEvent;
+
+    fn next(&mut self) -> Option<WinEvent> {
+        self.0.rx.recv().ok()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = WinEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WinEvent>> {
+        if let Ok(evt) = self.rx.try_recv() {
+            return Poll::Ready(Some(evt));
+        }
+
+        // wake after being queued, check again for the one sent meanwhile
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        match self.rx.try_recv() {
+            Ok(evt) => Poll::Ready(Some(evt)),
+            Err(_) => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.hub.unsubscribe(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bindings::Windows::Win32::UI::WindowsAndMessaging::EVENT_OBJECT_LOCATIONCHANGE;
+    use futures::{executor::block_on, StreamExt};
+
+    use crate::win2::{backend::WindowSystem, fake::{FakeDesktop, FakeWindow}, rect::Rect, window_event::WinEventType};
+
+    use super::Events;
+
+    #[test]
+    fn test_event_stream() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)));
+        let other = desktop.create(FakeWindow::new("Other", "other", Rect::new(0, 0, 100, 100)));
+
+        let stream = Events::with_system(target, desktop.clone())
+            .of(WinEventType::Hide)
+            .of(WinEventType::LocationChange)
+            .filter(|evt| evt.raw_id_object == 0)
+            // both apply
+            .filter(|evt| evt.etype != WinEventType::Hidden)
+            .stream()
+            .unwrap();
+
+        desktop.move_to(other.hwnd, Rect::new(5, 5, 100, 100));
+        desktop.set_visible(target.hwnd, false);
+        desktop.set_title(target.hwnd, "renamed");
+        desktop.move_to(target.hwnd, Rect::new(5, 5, 100, 100));
+
+        desktop.emit_object(EVENT_OBJECT_LOCATIONCHANGE, target.hwnd, -8, 0);
+
+        let types = std::iter::from_fn(|| stream.try_next()).map(|e| e.etype).collect::<Vec<_>>();
+        assert_eq!(types, vec![WinEventType::LocationChange]);
+
+        // the hub thread routes them for the iterator
+        desktop.minimize(target.hwnd);
+        desktop.set_visible(target.hwnd, true);
+        desktop.move_to(target.hwnd, Rect::new(9, 9, 100, 100));
+        assert_eq!(stream.iter().next().map(|e| e.etype), Some(WinEventType::Minimized));
+        assert_eq!(stream.into_iter().map(|e| e.etype).next(), Some(WinEventType::LocationChange));
+    }
+
+    #[test]
+    fn test_event_stream_async() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)));
+
+        let mut stream = Events::with_system(target, desktop.clone())
+            .filter(|evt| evt.etype == WinEventType::LocationChange)
+            .stream()
+            .unwrap();
+
+        let _desktop = desktop.clone();
+        let mover = std::thread::spawn(move || {
+            for x in 1..=3 {
+                std::thread::sleep(Duration::from_millis(5));
+                _desktop.move_to(target.hwnd, Rect::new(x, 0, 100, 100));
+            }
+        });
+
+        let moved = block_on(async {
+            let mut moved = vec![];
+            while let Some(evt) = stream.next().await {
+                moved.push(evt.window);
+                if moved.len() == 3 {
+                    break;
+                }
+            }
+            moved
+        });
+        mover.join().unwrap();
+
+        assert_eq!(moved, vec![target; 3]);
+        drop(stream);
+        assert_eq!(desktop.hooks(), vec![]);
+    }
+}
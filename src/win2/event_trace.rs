@@ -0,0 +1,433 @@
+// record window events with snapshots of the window to a JSON-lines
+// trace, and replay it later: into a listener, or headless onto a
+// fake desktop to reproduce what a customer saw.
+//
+// The times are the ones of the hooks, the snapshots are taken when
+// the hub routes the event, a moment later, so a window changing
+// fast may be recorded a step ahead.
+//
+//     let recorder = Events::new(Window::default()).record(BufWriter::new(File::create("attach.trace")?))?;
+//     ..
+//     recorder.finish()?;
+//
+//     Trace::load("attach.trace")?.replay_on(&desktop, 10.0);
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bindings::Windows::Win32::Foundation::HWND;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::EVENT_OBJECT_DESTROY;
+use serde::{Deserialize, Serialize};
+
+use super::error::Result;
+use super::event_hub::{EventHub, SubscriberId};
+use super::event_stream::Events;
+use super::fake::{FakeDesktop, FakeWindow};
+use super::rect::Rect;
+use super::window::Window;
+use super::window_event::{event_name, WinEvent, WinEventListener};
+
+// one line of a trace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    // time of the event since the first one recorded
+    pub at_ms: u64,
+    pub event: u32,
+    // for people reading the trace, not used by replay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub hwnd: isize,
+    #[serde(default)]
+    pub pid: u32,
+    #[serde(default)]
+    pub tid: u32,
+    #[serde(default)]
+    pub object: i32,
+    #[serde(default)]
+    pub child: i32,
+
+    // the window when the event was routed, none when it was gone
+    #[serde(default)]
+    pub rect: Option<Rect>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub visible: bool,
+}
+
+impl TraceRecord {
+    pub fn event(&self) -> WinEvent {
+        let w = Window::new(HWND(self.hwnd), self.pid, self.tid);
+        let mut evt = WinEvent::with_window(Default::default(), self.event, w);
+        evt.raw_id_object = self.object;
+        evt.raw_id_child = self.child;
+        evt.raw_event_time = self.at_ms as u32;
+        evt
+    }
+}
+
+struct TraceWriter {
+    out: Box<dyn Write + Send>,
+    // the first one, later writes are skipped and
+    // every flush fails with it
+    error: Option<(ErrorKind, String)>,
+}
+
+impl TraceWriter {
+    fn write(&mut self, record: &TraceRecord) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let line = serde_json::to_string(record).map_err(Error::from);
+        if let Err(e) = line.and_then(|line| writeln!(self.out, "{}", line)) {
+            self.error = Some((e.kind(), e.to_string()));
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some((kind, msg)) = &self.error {
+            return Err(Error::new(*kind, msg.clone()));
+        }
+        self.out.flush()
+    }
+}
+
+// writes the events until finished or dropped
+pub struct TraceRecorder {
+    hub: Arc<EventHub>,
+    id: Option<SubscriberId>,
+    out: Arc<Mutex<TraceWriter>>,
+}
+
+impl Events {
+    // write the events to `out`, one JSON object per line
+    pub fn record<W: Write + Send + 'static>(&mut self, out: W) -> Result<TraceRecorder> {
+        let out = Arc::new(Mutex::new(TraceWriter { out: Box::new(out), error: None }));
+        let (_out, sys) = (out.clone(), self.sys.clone());
+        let mut first = None;
+
+        let (hub, id) = self.subscribe(move |evt: &WinEvent| {
+            let hwnd = evt.window.hwnd;
+            let first = *first.get_or_insert(evt.raw_event_time);
+            let record = TraceRecord {
+                at_ms: evt.raw_event_time.wrapping_sub(first) as u64,
+                event: evt.raw_event,
+                name: event_name(evt.raw_event).map(str::to_string),
+                hwnd: hwnd.0,
+                pid: evt.window.pid,
+                tid: evt.window.tid,
+                object: evt.raw_id_object,
+                child: evt.raw_id_child,
+
+                rect: sys.rect(hwnd).ok(),
+                title: sys.title(hwnd),
+                class: sys.class(hwnd).ok(),
+                visible: sys.is_visible(hwnd),
+            };
+            _out.lock().unwrap().write(&record);
+        })?;
+
+        Ok(TraceRecorder { hub, id: Some(id), out })
+    }
+}
+
+impl TraceRecorder {
+    // write what happened so far, the first error of a write if any
+    pub fn flush(&self) -> Result<()> {
+        // don't wait for the hub thread to route them
+        self.hub.dispatch();
+        self.out.lock().unwrap().flush()
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        self.stop();
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.hub.unsubscribe(id);
+        }
+    }
+}
+
+impl Drop for TraceRecorder {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    pub records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    // blank lines are skipped
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut records = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record = serde_json::from_str(&line)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("trace line {}: {}", i + 1, e)))?;
+            records.push(record);
+        }
+
+        Ok(Self { records })
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = WinEvent> + '_ {
+        self.records.iter().map(TraceRecord::event)
+    }
+
+    // call `f` with each record, waiting as long as between the
+    // recorded ones divided by `speed`; `f64::INFINITY` doesn't wait.
+    pub fn replay<F: FnMut(&TraceRecord)>(&self, speed: f64, mut f: F) {
+        let mut last = self.records.first().map_or(0, |r| r.at_ms);
+        for record in self.records.iter() {
+            let wait = delay(record.at_ms.saturating_sub(last), speed);
+            if !wait.is_zero() {
+                thread::sleep(wait);
+            }
+            last = record.at_ms;
+
+            f(record);
+        }
+    }
+
+    // hand the events to the handlers of `listener`, they see the
+    // recorded windows, not ones of this desktop
+    pub fn replay_into(&self, listener: &WinEventListener, speed: f64) {
+        self.replay(speed, |record| {
+            listener.inject(record.event());
+            listener.pump();
+        });
+    }
+
+    // recreate the windows on the fake desktop and send the events from
+    // there, to all its hooks, at the recorded times of the desktop.
+    // Returns the windows by recorded hwnd.
+    pub fn replay_on(&self, desktop: &FakeDesktop, speed: f64) -> HashMap<isize, Window> {
+        let mut windows = HashMap::new();
+        let mut seen = HashMap::new();
+        let mut last = self.records.first().map_or(0, |r| r.at_ms);
+
+        self.replay(speed, |record| {
+            desktop.advance(record.at_ms.saturating_sub(last) as u32);
+            last = record.at_ms;
+
+            let w = *windows.entry(record.hwnd).or_insert_with(|| {
+                desktop.insert(FakeWindow {
+                    pid: record.pid,
+                    tid: record.tid,
+                    ..FakeWindow::new(record.class.as_deref().unwrap_or(""), "", Rect::default())
+                })
+            });
+
+            // the window as it was after the event
+            if record.rect.is_some() || record.class.is_some() {
+                desktop.update(w.hwnd, |fake| {
+                    if let Some(rect) = record.rect {
+                        fake.rect = rect;
+                    }
+                    fake.title = record.title.clone();
+                    fake.visible = record.visible;
+                });
+            }
+
+            desktop.emit_object(record.event, w.hwnd, record.object, record.child);
+
+            if record.event == EVENT_OBJECT_DESTROY && record.object == 0 {
+                desktop.remove(w.hwnd);
+                windows.remove(&record.hwnd);
+                seen.insert(record.hwnd, w);
+            }
+        });
+
+        seen.extend(windows);
+        seen
+    }
+}
+
+// how long to wait for a gap in the recording
+fn delay(gap_ms: u64, speed: f64) -> Duration {
+    if !speed.is_finite() || speed <= 0.0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(gap_ms as f64 / 1000.0 / speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::win2::{fake::{FakeDesktop, FakeWindow}, rect::Rect, window::Window, window_event::{WinEvent, WinEventListener, WinEventType}};
+    use crate::win2::event_stream::Events;
+
+    use super::{delay, Trace};
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    type Seen = Arc<Mutex<Vec<(WinEventType, Option<Rect>, Option<String>)>>>;
+
+    // what a listener of all windows sees of the desktop
+    fn watch(desktop: &Arc<FakeDesktop>, seen: &Seen) -> WinEventListener {
+        let (_desktop, _seen) = (desktop.clone(), seen.clone());
+        let mut listener = WinEventListener::with_system(Window::default(), desktop.clone());
+        listener.on(WinEventType::All, move |evt: &WinEvent| {
+            let w = _desktop.get(evt.window.hwnd);
+            _seen.lock().unwrap().push((evt.etype, w.as_ref().map(|w| w.rect), w.and_then(|w| w.title)));
+        });
+        listener.install().unwrap();
+        listener
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let buf = Shared::default();
+        let recorder = Events::with_system(Window::default(), desktop.clone()).record(buf.clone()).unwrap();
+        let recorded: Seen = Default::default();
+        let listener = watch(&desktop, &recorded);
+
+        // the snapshots are taken when routed, flush after each step
+        desktop.advance(1000);
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)));
+        recorder.flush().unwrap();
+        desktop.advance(16);
+        desktop.emit_object(0x800B, target.hwnd, -8, 0);
+        for rect in [Rect::new(5, 0, 100, 100), Rect::new(10, 0, 100, 100)].iter() {
+            desktop.advance(16);
+            desktop.move_to(target.hwnd, *rect);
+            recorder.flush().unwrap();
+        }
+        desktop.advance(100);
+        desktop.set_title(target.hwnd, "renamed");
+        recorder.flush().unwrap();
+        desktop.destroy(target.hwnd);
+        recorder.finish().unwrap();
+        listener.pump();
+
+        let text = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let trace = Trace::from_reader(text.as_bytes()).unwrap();
+        let names = trace.records.iter().map(|r| r.name.as_deref().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, vec![
+            "EVENT_OBJECT_CREATE", "EVENT_OBJECT_SHOW", "EVENT_OBJECT_LOCATIONCHANGE",
+            "EVENT_OBJECT_LOCATIONCHANGE", "EVENT_OBJECT_LOCATIONCHANGE", "EVENT_OBJECT_NAMECHANGE",
+            "EVENT_OBJECT_HIDE", "EVENT_OBJECT_DESTROY",
+        ]);
+        assert_eq!(trace.records[2].object, -8);
+        assert_eq!(trace.records[4].rect, Some(Rect::new(10, 0, 100, 100)));
+        assert_eq!(trace.records[7].rect, None);
+        let times = trace.records.iter().map(|r| r.at_ms).collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 0, 16, 32, 48, 148, 148, 148]);
+
+        // headless on another desktop, listeners there see the same
+        let other = Arc::new(FakeDesktop::new());
+        let replayed: Seen = Default::default();
+        let listener = watch(&other, &replayed);
+        let windows = trace.replay_on(&other, f64::INFINITY);
+        listener.pump();
+
+        assert_eq!(*replayed.lock().unwrap(), recorded.lock().unwrap()[..]);
+        assert_eq!(windows.len(), 1);
+        assert!(other.get(windows[&target.hwnd.0].hwnd).is_none());
+
+        // or right into the handlers
+        let types = Arc::new(Mutex::new(vec![]));
+        let _types = types.clone();
+        let mut listener = WinEventListener::with_system(Window::default(), Arc::new(FakeDesktop::new()));
+        listener.on(WinEventType::LocationChange, move |evt: &WinEvent| _types.lock().unwrap().push(evt.window.hwnd));
+        trace.replay_into(&listener, 1000.0);
+        assert_eq!(*types.lock().unwrap(), vec![target.hwnd; 3]);
+    }
+
+    #[test]
+    fn test_trace_lines() {
+        let text = r#"
+{"at_ms": 0, "event": 32779, "hwnd": 42, "rect": {"x": 1, "y": 2, "width": 3, "height": 4}}
+
+{"at_ms": 15, "event": 32770, "hwnd": 42, "name": "EVENT_OBJECT_SHOW", "visible": true}
+"#;
+        let trace = Trace::from_reader(text.as_bytes()).unwrap();
+        let types = trace.events().map(|e| (e.etype, e.window.hwnd.0)).collect::<Vec<_>>();
+        assert_eq!(types, vec![(WinEventType::LocationChange, 42), (WinEventType::Shown, 42)]);
+        assert_eq!(trace.records[0].rect, Some(Rect::new(1, 2, 3, 4)));
+
+        let err = Trace::from_reader("{}\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().starts_with("trace line 1: "), "{}", err);
+
+        let cases = [
+            (1000, 1.0, Duration::from_secs(1)),
+            (1000, 4.0, Duration::from_millis(250)),
+            (1000, 0.5, Duration::from_secs(2)),
+            (1000, f64::INFINITY, Duration::ZERO),
+            (1000, 0.0, Duration::ZERO),
+            (0, 1.0, Duration::ZERO),
+        ];
+        for (gap, speed, expected) in cases.iter() {
+            assert_eq!(delay(*gap, *speed), *expected, "{} / {}", gap, speed);
+        }
+    }
+
+    // fails the first write only
+    struct FailOnce(bool);
+
+    impl Write for FailOnce {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.0, true) {
+                return Ok(buf.len());
+            }
+            Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "disk full"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_error_sticks() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let recorder = Events::with_system(Window::default(), desktop.clone()).record(FailOnce(false)).unwrap();
+
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)));
+        assert_eq!(recorder.flush().unwrap_err().kind(), std::io::ErrorKind::WriteZero);
+
+        // the trace has a gap, it keeps failing
+        desktop.move_to(target.hwnd, Rect::new(5, 0, 100, 100));
+        assert!(recorder.flush().is_err());
+        assert_eq!(recorder.finish().unwrap_err().to_string(), "disk full");
+    }
+}
@@ -0,0 +1,549 @@
+// in-memory fake desktop, tests can script windows appearing,
+// moving and dying, all events are sent to installed hooks
+// like the real global win event hook does.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use bindings::Windows::Win32::Foundation::HWND;
+use bindings::Windows::Win32::UI::Accessibility::HWINEVENTHOOK;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::{
+    EVENT_OBJECT_CLOAKED, EVENT_OBJECT_CREATE, EVENT_OBJECT_DESTROY, EVENT_OBJECT_HIDE,
+    EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_SHOW, EVENT_OBJECT_UNCLOAKED,
+    EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND, EVENT_SYSTEM_MINIMIZESTART,
+    EVENT_SYSTEM_MOVESIZEEND, EVENT_SYSTEM_MOVESIZESTART,
+};
+
+use super::backend::{EventChannel, WindowSystem};
+use super::dpi::ScaleFactor;
+use super::error::Result;
+use super::monitor::Monitor;
+use super::rect::{Insets, Rect};
+use super::window::Window;
+use super::window_event::{HookScope, WinEvent, WinEventType};
+
+// scripted window on the fake desktop
+#[derive(Debug, Clone, Default)]
+pub struct FakeWindow {
+    pub pid: u32,
+    pub tid: u32,
+    pub title: Option<String>,
+    pub class: String,
+    pub exe_path: String,
+    pub rect: Rect,
+    pub visible: bool,
+    pub owner: HWND,
+    // a child window when set
+    pub parent: HWND,
+    pub topmost: bool,
+    pub scale: ScaleFactor,
+    // border and caption around the client area
+    pub frame: Insets,
+    pub click_through: bool,
+    pub minimized: bool,
+    pub maximized: bool,
+    pub cloaked: bool,
+}
+
+impl FakeWindow {
+    pub fn new(class: &str, title: &str, rect: Rect) -> Self {
+        Self {
+            pid: 1,
+            tid: 1,
+            title: if title.is_empty() { None } else { Some(title.to_string()) },
+            class: class.to_string(),
+            rect,
+            visible: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+struct FakeState {
+    next_hwnd: isize,
+    next_hook: isize,
+    // keep the z order, first created first
+    windows: BTreeMap<isize, FakeWindow>,
+    // bottom to top
+    z_order: Vec<isize>,
+    hooks: HashMap<isize, (EventChannel, HookScope)>,
+    // new hooks fail, like `SetWinEventHook` out of resources
+    hooks_failing: bool,
+    // time of the events, only moved by `advance`
+    now_ms: u32,
+    monitors: Vec<Monitor>,
+}
+
+pub struct FakeDesktop {
+    state: Mutex<FakeState>,
+}
+
+impl Default for FakeDesktop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn not_found(hwnd: HWND) -> Error {
+    Error::new(ErrorKind::NotFound, format!("no such window {}", hwnd.0))
+}
+
+impl FakeDesktop {
+
+    // desktop with one 1920x1080 monitor, taskbar at the bottom
+    pub fn new() -> Self {
+        let monitor = Monitor::new(Rect::new(0, 0, 1920, 1080), Rect::new(0, 0, 1920, 1040))
+            .with_primary(true);
+
+        FakeDesktop {
+            state: Mutex::new(FakeState {
+                monitors: vec![monitor],
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn set_monitors(&self, monitors: Vec<Monitor>) {
+        self.state.lock().unwrap().monitors = monitors;
+    }
+
+    // add a window, sends create and show if visible
+    pub fn create(&self, w: FakeWindow) -> Window {
+        let visible = w.visible;
+        let window = self.insert(w);
+
+        self.emit(EVENT_OBJECT_CREATE, window.hwnd);
+        if visible {
+            self.emit(EVENT_OBJECT_SHOW, window.hwnd);
+        }
+
+        window
+    }
+
+    // add a window without any event
+    pub fn insert(&self, w: FakeWindow) -> Window {
+        let mut state = self.state.lock().unwrap();
+        state.next_hwnd += 1;
+        let hwnd = HWND(state.next_hwnd);
+        let window = Window::new(hwnd, w.pid, w.tid);
+        state.windows.insert(hwnd.0, w);
+        state.z_order.push(hwnd.0);
+        window
+    }
+
+    // remove the window without any event
+    pub fn remove(&self, hwnd: HWND) -> Option<FakeWindow> {
+        let mut state = self.state.lock().unwrap();
+        state.z_order.retain(|h| *h != hwnd.0);
+        state.windows.remove(&hwnd.0)
+    }
+
+    // remove the window, sends hide and destroy
+    pub fn destroy(&self, hwnd: HWND) {
+        let w = self.window(hwnd);
+        if let Some(removed) = self.remove(hwnd) {
+            if removed.visible {
+                self.send(EVENT_OBJECT_HIDE, w);
+            }
+            self.send(EVENT_OBJECT_DESTROY, w);
+        }
+    }
+
+    // move or resize the window like a program does
+    pub fn move_to(&self, hwnd: HWND, rect: Rect) {
+        if self.update(hwnd, |w| w.rect = rect) {
+            self.emit(EVENT_OBJECT_LOCATIONCHANGE, hwnd);
+        }
+    }
+
+    // drag the window by user through all rects
+    pub fn drag(&self, hwnd: HWND, rects: &[Rect]) {
+        self.emit(EVENT_SYSTEM_MOVESIZESTART, hwnd);
+        for rect in rects {
+            self.move_to(hwnd, *rect);
+        }
+        self.emit(EVENT_SYSTEM_MOVESIZEEND, hwnd);
+    }
+
+    // move the window to a monitor with other scale
+    pub fn set_scale(&self, hwnd: HWND, scale: ScaleFactor) {
+        self.update(hwnd, |w| w.scale = scale);
+    }
+
+    pub fn set_title(&self, hwnd: HWND, title: &str) {
+        let title = if title.is_empty() { None } else { Some(title.to_string()) };
+        if self.update(hwnd, |w| w.title = title) {
+            self.emit(EVENT_OBJECT_NAMECHANGE, hwnd);
+        }
+    }
+
+    pub fn minimize(&self, hwnd: HWND) {
+        if self.update(hwnd, |w| w.minimized = true) {
+            self.emit(EVENT_SYSTEM_MINIMIZESTART, hwnd);
+        }
+    }
+
+    // back from minimized or maximized
+    pub fn restore(&self, hwnd: HWND, rect: Rect) {
+        let mut minimized = false;
+        let found = self.update(hwnd, |w| {
+            minimized = w.minimized;
+            w.minimized = false;
+            w.maximized = false;
+            w.rect = rect;
+        });
+        if !found {
+            return;
+        }
+        if minimized {
+            self.emit(EVENT_SYSTEM_MINIMIZEEND, hwnd);
+        }
+        self.emit(EVENT_OBJECT_LOCATIONCHANGE, hwnd);
+    }
+
+    // maximized to the rect, e.g. the work area
+    pub fn maximize(&self, hwnd: HWND, rect: Rect) {
+        if self.update(hwnd, |w| { w.maximized = true; w.minimized = false; w.rect = rect; }) {
+            self.emit(EVENT_OBJECT_LOCATIONCHANGE, hwnd);
+        }
+    }
+
+    // moved to another virtual desktop and back
+    pub fn cloak(&self, hwnd: HWND, cloaked: bool) {
+        if self.update(hwnd, |w| w.cloaked = cloaked) {
+            self.emit(if cloaked { EVENT_OBJECT_CLOAKED } else { EVENT_OBJECT_UNCLOAKED }, hwnd);
+        }
+    }
+
+    // activated by user, on top of all others
+    pub fn bring_to_front(&self, hwnd: HWND) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if !state.windows.contains_key(&hwnd.0) {
+                return;
+            }
+            state.z_order.retain(|h| *h != hwnd.0);
+            state.z_order.push(hwnd.0);
+        }
+        self.emit(EVENT_SYSTEM_FOREGROUND, hwnd);
+    }
+
+    // windows from top to bottom
+    pub fn z_order(&self) -> Vec<HWND> {
+        self.state.lock().unwrap().z_order.iter().rev().map(|h| HWND(*h)).collect()
+    }
+
+    // get the scripted state of the window
+    pub fn get(&self, hwnd: HWND) -> Option<FakeWindow> {
+        self.state.lock().unwrap().windows.get(&hwnd.0).cloned()
+    }
+
+    // send a raw event for the window to all hooks
+    pub fn emit(&self, event: u32, hwnd: HWND) {
+        self.emit_object(event, hwnd, 0, 0);
+    }
+
+    // like `emit`, for a part of the window like its caret
+    pub fn emit_object(&self, event: u32, hwnd: HWND, id_object: i32, id_child: i32) {
+        let w = self.window(hwnd);
+        self.send_object(event, w, id_object, id_child);
+    }
+
+    fn send(&self, event: u32, w: Window) {
+        self.send_object(event, w, 0, 0);
+    }
+
+    fn send_object(&self, event: u32, w: Window, id_object: i32, id_child: i32) {
+        // collect channels first, handlers may call back into us
+        let (hooks, now): (Vec<(isize, EventChannel)>, u32) = {
+            let state = self.state.lock().unwrap();
+            let hooks = state.hooks.iter()
+                .filter(|(_, (_, scope))| scope.contains(event, &w))
+                .map(|(k, (ch, _))| (*k, ch.clone()))
+                .collect();
+            (hooks, state.now_ms)
+        };
+
+        for (hook, ch) in hooks {
+            let mut evt = WinEvent::with_window(HWINEVENTHOOK(hook), event, w);
+            evt.raw_id_object = id_object;
+            evt.raw_id_child = id_child;
+            evt.raw_event_time = now;
+            if evt.etype == WinEventType::Unknown {
+                continue;
+            }
+
+            let _ = ch.lock().unwrap().0.send(evt);
+        }
+    }
+
+    // move the time of the events on
+    pub fn advance(&self, ms: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.now_ms = state.now_ms.wrapping_add(ms);
+    }
+
+    // let installing hooks fail from now on
+    pub fn fail_hooks(&self, fail: bool) {
+        self.state.lock().unwrap().hooks_failing = fail;
+    }
+
+    // scopes of the installed hooks, in install order
+    pub fn hooks(&self) -> Vec<HookScope> {
+        let state = self.state.lock().unwrap();
+        let mut hooks = state.hooks.iter().collect::<Vec<_>>();
+        hooks.sort_by_key(|(id, _)| **id);
+        hooks.into_iter().map(|(_, (_, scope))| *scope).collect()
+    }
+
+    // change the window without any event, false if there is none
+    pub fn update(&self, hwnd: HWND, f: impl FnOnce(&mut FakeWindow)) -> bool {
+        match self.state.lock().unwrap().windows.get_mut(&hwnd.0) {
+            Some(w) => { f(w); true },
+            None => false,
+        }
+    }
+
+    fn with<T>(&self, hwnd: HWND, f: impl FnOnce(&FakeWindow) -> T) -> Result<T> {
+        match self.state.lock().unwrap().windows.get(&hwnd.0) {
+            Some(w) => Ok(f(w)),
+            None => Err(not_found(hwnd)),
+        }
+    }
+}
+
+impl WindowSystem for FakeDesktop {
+
+    fn is_window(&self, hwnd: HWND) -> bool {
+        self.state.lock().unwrap().windows.contains_key(&hwnd.0)
+    }
+
+    fn pid_tid(&self, hwnd: HWND) -> (u32, u32) {
+        self.with(hwnd, |w| (w.pid, w.tid)).unwrap_or((0, 0))
+    }
+
+    fn title(&self, hwnd: HWND) -> Option<String> {
+        self.with(hwnd, |w| w.title.clone()).ok().flatten()
+    }
+
+    fn class(&self, hwnd: HWND) -> Result<String> {
+        self.with(hwnd, |w| w.class.clone())
+    }
+
+    fn exe_path(&self, hwnd: HWND) -> Result<String> {
+        self.with(hwnd, |w| w.exe_path.clone())
+    }
+
+    fn rect(&self, hwnd: HWND) -> Result<Rect> {
+        self.with(hwnd, |w| w.rect)
+    }
+
+    fn set_rect(&self, hwnd: HWND, rect: &Rect, top: bool) -> Result<()> {
+        if !self.update(hwnd, |w| { w.rect = *rect; w.topmost = top; }) {
+            return Err(not_found(hwnd));
+        }
+        self.emit(EVENT_OBJECT_LOCATIONCHANGE, hwnd);
+
+        Ok(())
+    }
+
+    fn set_rect_above(&self, hwnd: HWND, rect: &Rect, above: HWND) -> Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            match state.windows.get_mut(&hwnd.0) {
+                Some(w) => { w.rect = *rect; w.topmost = false; },
+                None => return Err(not_found(hwnd)),
+            }
+            // take ourself out first, then find the place
+            let mut z: Vec<isize> = state.z_order.iter().copied().filter(|h| *h != hwnd.0).collect();
+            if let Some(i) = z.iter().position(|h| *h == above.0) {
+                z.insert(i + 1, hwnd.0);
+                state.z_order = z;
+            }
+        }
+        self.emit(EVENT_OBJECT_LOCATIONCHANGE, hwnd);
+
+        Ok(())
+    }
+
+    fn client_rect(&self, hwnd: HWND) -> Result<Rect> {
+        self.with(hwnd, |w| w.rect.inset(w.frame))
+    }
+
+    fn scale_factor(&self, hwnd: HWND) -> ScaleFactor {
+        self.with(hwnd, |w| w.scale).unwrap_or_default()
+    }
+
+    fn is_visible(&self, hwnd: HWND) -> bool {
+        self.with(hwnd, |w| w.visible).unwrap_or(false)
+    }
+
+    fn set_visible(&self, hwnd: HWND, visible: bool) {
+        let mut changed = false;
+        self.update(hwnd, |w| {
+            changed = w.visible != visible;
+            w.visible = visible;
+        });
+
+        if changed {
+            self.emit(if visible { EVENT_OBJECT_SHOW } else { EVENT_OBJECT_HIDE }, hwnd);
+        }
+    }
+
+    fn is_minimized(&self, hwnd: HWND) -> bool {
+        self.with(hwnd, |w| w.minimized).unwrap_or(false)
+    }
+
+    fn is_maximized(&self, hwnd: HWND) -> bool {
+        self.with(hwnd, |w| w.maximized).unwrap_or(false)
+    }
+
+    fn is_cloaked(&self, hwnd: HWND) -> bool {
+        self.with(hwnd, |w| w.cloaked).unwrap_or(false)
+    }
+
+    fn set_click_through(&self, hwnd: HWND, enable: bool) -> Result<()> {
+        if !self.update(hwnd, |w| w.click_through = enable) {
+            return Err(not_found(hwnd));
+        }
+        Ok(())
+    }
+
+    fn owner(&self, hwnd: HWND) -> HWND {
+        self.with(hwnd, |w| w.owner).unwrap_or_default()
+    }
+
+    fn set_owner(&self, child: HWND, owner: HWND) -> Result<()> {
+        if !self.update(child, |w| w.owner = owner) {
+            return Err(not_found(child));
+        }
+        Ok(())
+    }
+
+    fn is_top_level(&self, hwnd: HWND) -> bool {
+        self.with(hwnd, |w| w.parent.0 == 0).unwrap_or(false)
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        self.state.lock().unwrap().monitors.clone()
+    }
+
+    fn enum_windows(&self, f: &mut dyn FnMut(Window) -> bool) -> bool {
+        let windows: Vec<Window> = self.state.lock().unwrap()
+            .windows.iter()
+            .map(|(hwnd, w)| Window::new(HWND(*hwnd), w.pid, w.tid))
+            .collect();
+
+        for w in windows {
+            if !f(w) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn install_hook(&self, ch: EventChannel, scope: HookScope) -> Result<isize> {
+        let mut state = self.state.lock().unwrap();
+        if state.hooks_failing {
+            return Err(Error::other(format!("can't hook {:?}", scope)));
+        }
+        state.next_hook += 1;
+        let hook = state.next_hook;
+        state.hooks.insert(hook, (ch, scope));
+
+        Ok(hook)
+    }
+
+    fn remove_hook(&self, hook: isize) {
+        self.state.lock().unwrap().hooks.remove(&hook);
+    }
+
+    fn run_loop(&self, cb: &mut dyn FnMut() -> bool) {
+        while cb() {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::win2::{backend::WindowSystem, rect::Rect, window::Window, window_event::{WinEvent, WinEventListener, WinEventType}};
+
+    use super::{FakeDesktop, FakeWindow};
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn test_fake_window_lifecycle() {
+        let desktop = FakeDesktop::new();
+        let w = desktop.create(FakeWindow::new("Notepad", "untitled", rect(0, 0, 100, 100)));
+
+        assert!(desktop.is_window(w.hwnd));
+        assert_eq!(desktop.class(w.hwnd).unwrap(), "Notepad");
+        assert_eq!(desktop.title(w.hwnd), Some("untitled".to_string()));
+
+        desktop.move_to(w.hwnd, rect(10, 20, 100, 100));
+        assert_eq!(desktop.rect(w.hwnd).unwrap(), rect(10, 20, 100, 100));
+
+        desktop.destroy(w.hwnd);
+        assert!(!desktop.is_window(w.hwnd));
+        assert!(desktop.rect(w.hwnd).is_err());
+    }
+
+    #[test]
+    fn test_fake_enum_windows() {
+        let desktop = FakeDesktop::new();
+        let a = desktop.create(FakeWindow::new("A", "a", Rect::zero()));
+        let b = desktop.create(FakeWindow::new("B", "b", Rect::zero()));
+
+        let mut all = vec![];
+        desktop.enum_windows(&mut |w| { all.push(w); true });
+        assert_eq!(all, vec![a, b]);
+
+        let mut first = vec![];
+        assert!(!desktop.enum_windows(&mut |w| { first.push(w); false }));
+        assert_eq!(first, vec![a]);
+    }
+
+    #[test]
+    fn test_fake_events_dispatch() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(0, 0, 100, 100)));
+        let other = desktop.create(FakeWindow::new("Other", "other", rect(0, 0, 100, 100)));
+
+        let seen = Arc::new(Mutex::new(Vec::<(WinEventType, Window)>::new()));
+        let _seen = seen.clone();
+
+        let mut listener = WinEventListener::with_system(target, desktop.clone());
+        listener.on(WinEventType::All, move |evt: &WinEvent| {
+            _seen.lock().unwrap().push((evt.etype, evt.window));
+        });
+        listener.install().unwrap();
+
+        desktop.drag(target.hwnd, &[rect(5, 0, 100, 100), rect(10, 0, 100, 100)]);
+        desktop.move_to(other.hwnd, rect(50, 50, 100, 100));
+
+        // events of other windows never reach the listener
+        assert_eq!(listener.pump(), 4);
+        assert_eq!(*seen.lock().unwrap(), vec![
+            (WinEventType::MoveResizeStart, target),
+            (WinEventType::LocationChange, target),
+            (WinEventType::LocationChange, target),
+            (WinEventType::MoveResizeEnd, target),
+        ]);
+
+        // nothing after the hook removed
+        drop(listener);
+        desktop.move_to(target.hwnd, rect(0, 0, 100, 100));
+        assert_eq!(seen.lock().unwrap().len(), 4);
+    }
+}
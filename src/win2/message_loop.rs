@@ -14,11 +14,11 @@ use bindings::Windows::Win32::UI::WindowsAndMessaging::{
 pub struct MessageLoop;
 
 impl MessageLoop {
-    pub fn start(sleep: u64, cb: impl Fn(Option<MSG>) -> bool) {
+    pub fn start(sleep: u64, cb: impl FnMut(Option<MSG>) -> bool) {
         Self::start_with_sleep(sleep, cb);
     }
 
-    pub fn start_with_sleep(sleep: u64, cb: impl Fn(Option<MSG>) -> bool) {
+    pub fn start_with_sleep(sleep: u64, mut cb: impl FnMut(Option<MSG>) -> bool) {
         let mut msg: MSG = MSG::default();
         loop {
             let mut value: Option<MSG> = None;
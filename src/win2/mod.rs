@@ -1,7 +1,19 @@
 
 pub mod error;
 pub mod rect;
+pub mod point;
+pub mod dpi;
+pub mod monitor;
+pub mod coalesce;
 pub mod window;
+pub mod window_state;
+pub mod selector;
 pub mod window_enum;
 pub mod window_event;
-pub mod message_loop;
\ No newline at end of file
+pub mod event_hub;
+pub mod event_stream;
+pub mod event_filter;
+pub mod event_trace;
+pub mod message_loop;
+pub mod backend;
+pub mod fake;
\ No newline at end of file
@@ -0,0 +1,268 @@
+// monitors and the virtual screen made of them.
+//
+// The geometry here is platform independent so placement can be
+// tested with synthetic layouts, only `enum_monitors` talks to Win32.
+
+use bindings::Windows::Win32::{
+    Foundation::{
+        BOOL,
+        LPARAM,
+        RECT,
+    },
+    Graphics::Gdi::{
+        EnumDisplayMonitors,
+        GetMonitorInfoW,
+        HDC,
+        HMONITOR,
+        MONITORINFO,
+    },
+    UI::HiDpi::{
+        GetDpiForMonitor,
+        MDT_EFFECTIVE_DPI,
+    },
+    UI::WindowsAndMessaging::MONITORINFOF_PRIMARY,
+};
+
+use super::dpi::ScaleFactor;
+use super::point::Point;
+use super::rect::Rect;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Monitor {
+    // monitor handle, 0 for synthetic ones
+    pub handle: isize,
+    // whole monitor, in physical pixels
+    pub bounds: Rect,
+    // bounds without taskbar and docked app bars
+    pub work_area: Rect,
+    pub scale: ScaleFactor,
+    pub primary: bool,
+}
+
+impl Monitor {
+    pub fn new(bounds: Rect, work_area: Rect) -> Self {
+        Monitor {
+            handle: 0,
+            bounds,
+            work_area,
+            scale: ScaleFactor::default(),
+            primary: false,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: ScaleFactor) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_primary(mut self, primary: bool) -> Self {
+        self.primary = primary;
+        self
+    }
+}
+
+/// All monitors of the desktop.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VirtualScreen {
+    pub monitors: Vec<Monitor>,
+}
+
+impl VirtualScreen {
+    pub fn new(monitors: Vec<Monitor>) -> Self {
+        VirtualScreen { monitors }
+    }
+
+    // monitors of the real desktop
+    pub fn current() -> Self {
+        Self::new(enum_monitors())
+    }
+
+    /// Bounding box of all monitors.
+    pub fn bounds(&self) -> Rect {
+        self.monitors.iter()
+            .fold(Rect::zero(), |acc, m| acc.union(&m.bounds))
+    }
+
+    pub fn primary(&self) -> Option<&Monitor> {
+        self.monitors.iter()
+            .find(|m| m.primary)
+            .or_else(|| self.monitors.first())
+    }
+
+    pub fn monitor_from_point(&self, point: Point) -> Option<&Monitor> {
+        self.monitors.iter().find(|m| m.bounds.contains_point(point))
+    }
+
+    /// Monitor sharing the largest area with the rect, `None` when the
+    /// rect is on no monitor at all.
+    pub fn monitor_containing(&self, rect: &Rect) -> Option<&Monitor> {
+        self.monitors.iter()
+            .filter_map(|m| m.bounds.intersect(rect).map(|i| (m, i.area())))
+            .fold(None, |best: Option<(&Monitor, i64)>, (m, area)| match best {
+                Some((_, a)) if a >= area => best,
+                _ => Some((m, area)),
+            })
+            .map(|(m, _)| m)
+    }
+
+    /// Like `monitor_containing`, but falls back to the monitor
+    /// closest to the rect when it is off screen.
+    pub fn nearest(&self, rect: &Rect) -> Option<&Monitor> {
+        if let Some(m) = self.monitor_containing(rect) {
+            return Some(m);
+        }
+
+        self.monitors.iter().min_by_key(|m| {
+            let gap = m.bounds.gap(rect);
+            gap.width as i64 * gap.width as i64 + gap.height as i64 * gap.height as i64
+        })
+    }
+
+    /// Move the rect into the work area of its nearest monitor.
+    pub fn clamp_to_work_area(&self, rect: &Rect) -> Rect {
+        match self.nearest(rect) {
+            Some(m) => rect.clamp_into(&m.work_area),
+            None => *rect,
+        }
+    }
+
+    /// Every pixel of the rect is inside one work area.
+    pub fn is_fully_visible(&self, rect: &Rect) -> bool {
+        self.monitors.iter().any(|m| m.work_area.contains_rect(*rect))
+    }
+}
+
+impl From<MONITORINFO> for Monitor {
+    fn from(info: MONITORINFO) -> Self {
+        Monitor::new(info.rcMonitor.into(), info.rcWork.into())
+            .with_primary(info.dwFlags & MONITORINFOF_PRIMARY != 0)
+    }
+}
+
+#[allow(non_snake_case)]
+unsafe extern "system" fn thunk(hmonitor: HMONITOR, _hdc: HDC, _rect: *mut RECT, lParam: LPARAM) -> BOOL {
+    let monitors = &mut *(lParam.0 as *mut Vec<Monitor>);
+
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !GetMonitorInfoW(hmonitor, &mut info).as_bool() {
+        return true.into();
+    }
+
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    let _ = GetDpiForMonitor(hmonitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y);
+
+    let mut monitor = Monitor::from(info).with_scale(ScaleFactor::from_dpi(dpi_x));
+    monitor.handle = hmonitor.0;
+    monitors.push(monitor);
+
+    true.into()
+}
+
+// enum all monitors of the desktop
+pub fn enum_monitors() -> Vec<Monitor> {
+    let mut monitors: Vec<Monitor> = vec![];
+    unsafe {
+        EnumDisplayMonitors(
+            HDC(0),
+            std::ptr::null(),
+            Some(thunk),
+            LPARAM(&mut monitors as *mut _ as _),
+        );
+    }
+
+    monitors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::win2::{dpi::ScaleFactor, point::Point, rect::Rect};
+
+    use super::{Monitor, VirtualScreen};
+
+    // 1920x1080 primary with bottom taskbar, 2560x1440 at 150% on the
+    // right, aligned at the top
+    fn layout() -> VirtualScreen {
+        VirtualScreen::new(vec![
+            Monitor::new(Rect::new(0, 0, 1920, 1080), Rect::new(0, 0, 1920, 1040))
+                .with_primary(true),
+            Monitor::new(Rect::new(1920, 0, 2560, 1440), Rect::new(1920, 0, 2560, 1380))
+                .with_scale(ScaleFactor(1.5)),
+        ])
+    }
+
+    #[test]
+    fn test_bounds_and_primary() {
+        let screen = layout();
+        assert_eq!(screen.bounds(), Rect::new(0, 0, 4480, 1440));
+        assert_eq!(screen.primary().unwrap().bounds, Rect::new(0, 0, 1920, 1080));
+        assert_eq!(VirtualScreen::default().bounds(), Rect::zero());
+        assert_eq!(VirtualScreen::default().primary(), None);
+    }
+
+    #[test]
+    fn test_monitor_from_point() {
+        let screen = layout();
+        assert_eq!(screen.monitor_from_point(Point::new(1919, 10)).unwrap().bounds.x, 0);
+        assert_eq!(screen.monitor_from_point(Point::new(1920, 10)).unwrap().bounds.x, 1920);
+        assert_eq!(screen.monitor_from_point(Point::new(100, 1200)), None);
+    }
+
+    #[test]
+    fn test_monitor_containing() {
+        let screen = layout();
+        let cases = [
+            (Rect::new(100, 100, 200, 200), Some(0)),
+            // mostly on the right one
+            (Rect::new(1900, 100, 200, 200), Some(1920)),
+            (Rect::new(1800, 100, 200, 200), Some(0)),
+            // below the primary, only the right one is that tall
+            (Rect::new(100, 1200, 200, 200), None),
+            (Rect::new(-500, 0, 100, 100), None),
+        ];
+
+        for (rect, x) in cases.iter() {
+            assert_eq!(screen.monitor_containing(rect).map(|m| m.bounds.x), *x, "{:?}", rect);
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        let screen = layout();
+        let cases = [
+            (Rect::new(100, 1200, 200, 200), 0),
+            (Rect::new(1700, 1300, 200, 100), 1920),
+            (Rect::new(-500, 0, 100, 100), 0),
+            (Rect::new(5000, 0, 100, 100), 1920),
+        ];
+
+        for (rect, x) in cases.iter() {
+            assert_eq!(screen.nearest(rect).unwrap().bounds.x, *x, "{:?}", rect);
+        }
+        assert_eq!(VirtualScreen::default().nearest(&Rect::zero()), None);
+    }
+
+    #[test]
+    fn test_clamp_to_work_area() {
+        let screen = layout();
+        let cases = [
+            // already visible
+            (Rect::new(100, 100, 200, 200), Rect::new(100, 100, 200, 200)),
+            // under the taskbar
+            (Rect::new(100, 1000, 200, 200), Rect::new(100, 840, 200, 200)),
+            // off the right edge of the virtual screen
+            (Rect::new(4400, 100, 200, 200), Rect::new(4280, 100, 200, 200)),
+            // off the left edge
+            (Rect::new(-50, 100, 200, 200), Rect::new(0, 100, 200, 200)),
+        ];
+
+        for (rect, expected) in cases.iter() {
+            let clamped = screen.clamp_to_work_area(rect);
+            assert_eq!(clamped, *expected, "{:?}", rect);
+            assert!(screen.is_fully_visible(&clamped));
+        }
+        assert!(!screen.is_fully_visible(&Rect::new(1900, 100, 200, 200)));
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt::{Display, Error, Formatter};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use bindings::Windows::Win32::Foundation::{POINT, SIZE};
+
+/// Position on the screen, relative to top left of screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Width and height of something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Size {
+    pub width:  i32,
+    pub height: i32,
+}
+
+/// Movement between two points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Offset {
+    pub dx: i32,
+    pub dy: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Self {
+        Point { x, y }
+    }
+
+    pub fn zero() -> Self {
+        Point::default()
+    }
+}
+
+impl Size {
+    pub fn new(width: i32, height: i32) -> Self {
+        Size { width, height }
+    }
+
+    pub fn zero() -> Self {
+        Size::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+}
+
+impl Offset {
+    pub fn new(dx: i32, dy: i32) -> Self {
+        Offset { dx, dy }
+    }
+
+    pub fn zero() -> Self {
+        Offset::default()
+    }
+}
+
+impl Add<Offset> for Point {
+    type Output = Point;
+
+    fn add(self, offset: Offset) -> Point {
+        Point::new(self.x + offset.dx, self.y + offset.dy)
+    }
+}
+
+impl AddAssign<Offset> for Point {
+    fn add_assign(&mut self, offset: Offset) {
+        *self = *self + offset;
+    }
+}
+
+impl Sub<Offset> for Point {
+    type Output = Point;
+
+    fn sub(self, offset: Offset) -> Point {
+        Point::new(self.x - offset.dx, self.y - offset.dy)
+    }
+}
+
+impl SubAssign<Offset> for Point {
+    fn sub_assign(&mut self, offset: Offset) {
+        *self = *self - offset;
+    }
+}
+
+// the offset to move from other to self
+impl Sub<Point> for Point {
+    type Output = Offset;
+
+    fn sub(self, other: Point) -> Offset {
+        Offset::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Add for Offset {
+    type Output = Offset;
+
+    fn add(self, other: Offset) -> Offset {
+        Offset::new(self.dx + other.dx, self.dy + other.dy)
+    }
+}
+
+impl Neg for Offset {
+    type Output = Offset;
+
+    fn neg(self) -> Offset {
+        Offset::new(-self.dx, -self.dy)
+    }
+}
+
+// scale the size, rounded to nearest pixel
+impl Mul<f64> for Size {
+    type Output = Size;
+
+    fn mul(self, scale: f64) -> Size {
+        Size::new(
+            (self.width as f64 * scale).round() as i32,
+            (self.height as f64 * scale).round() as i32,
+        )
+    }
+}
+
+impl Mul<i32> for Size {
+    type Output = Size;
+
+    fn mul(self, scale: i32) -> Size {
+        Size::new(self.width * scale, self.height * scale)
+    }
+}
+
+impl From<POINT> for Point {
+    fn from(p: POINT) -> Self {
+        Point::new(p.x, p.y)
+    }
+}
+
+impl From<Point> for POINT {
+    fn from(p: Point) -> Self {
+        POINT { x: p.x, y: p.y }
+    }
+}
+
+impl From<SIZE> for Size {
+    fn from(s: SIZE) -> Self {
+        Size::new(s.cx, s.cy)
+    }
+}
+
+impl From<Size> for SIZE {
+    fn from(s: Size) -> Self {
+        SIZE { cx: s.width, cy: s.height }
+    }
+}
+
+impl Display for Point {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bindings::Windows::Win32::Foundation::{POINT, SIZE};
+
+    use super::{Offset, Point, Size};
+
+    #[test]
+    fn test_point_offset() {
+        let p = Point::new(10, 20);
+        assert_eq!(p + Offset::new(5, -5), Point::new(15, 15));
+        assert_eq!(p - Offset::new(5, -5), Point::new(5, 25));
+        assert_eq!(Point::new(15, 15) - p, Offset::new(5, -5));
+        assert_eq!(p + (Point::new(0, 0) - p), Point::zero());
+
+        let mut q = p;
+        q += Offset::new(1, 2);
+        q -= -Offset::new(1, 2);
+        assert_eq!(q, Point::new(12, 24));
+    }
+
+    #[test]
+    fn test_size_scale() {
+        assert_eq!(Size::new(100, 50) * 1.5, Size::new(150, 75));
+        assert_eq!(Size::new(3, 5) * 0.5, Size::new(2, 3));
+        assert_eq!(Size::new(3, 5) * 2, Size::new(6, 10));
+        assert!(Size::new(0, 5).is_empty());
+    }
+
+    #[test]
+    fn test_win32_conversion() {
+        let p: POINT = Point::new(-3, 4).into();
+        assert_eq!((p.x, p.y), (-3, 4));
+        assert_eq!(Point::from(p), Point::new(-3, 4));
+
+        let s: SIZE = Size::new(7, 8).into();
+        assert_eq!((s.cx, s.cy), (7, 8));
+        assert_eq!(Size::from(s), Size::new(7, 8));
+    }
+}
@@ -1,9 +1,17 @@
+use std::cmp;
 use std::fmt::{Display, Error, Formatter};
 
 use bindings::Windows::Win32::Foundation::RECT;
+use serde::{Deserialize, Serialize};
+
+use super::point::{Offset, Point, Size};
 
 /// x & y coordinates are relative to top left of screen
-#[derive(Debug, Clone, Copy, PartialEq)]
+///
+/// Edges follow Win32 `RECT`: left and top are inside the rect,
+/// right (`x + width`) and bottom (`y + height`) are outside.
+/// A rect with zero or negative width or height is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Rect {
     pub x:      i32,
     pub y:      i32,
@@ -12,11 +20,44 @@ pub struct Rect {
 }
 
 impl Rect {
-    pub fn contains_point(self, point: (i32, i32)) -> bool {
-        point.0 >= self.x
-            && point.0 <= self.x + self.width
-            && point.1 >= self.y
-            && point.1 <= self.y + self.height
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn from_point_size(point: Point, size: Size) -> Self {
+        Rect {
+            x:      point.x,
+            y:      point.y,
+            width:  size.width,
+            height: size.height,
+        }
+    }
+
+    /// Create from edges, right and bottom are exclusive.
+    pub fn from_ltrb(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Rect {
+            x:      left,
+            y:      top,
+            width:  right - left,
+            height: bottom - top,
+        }
+    }
+
+    /// Point is inside when `left <= x < right` and `top <= y < bottom`.
+    pub fn contains_point(self, point: Point) -> bool {
+        point.x >= self.left()
+            && point.x < self.right()
+            && point.y >= self.top()
+            && point.y < self.bottom()
+    }
+
+    /// Every point of `other` is inside, an empty `other` is never contained.
+    pub fn contains_rect(self, other: Rect) -> bool {
+        !other.is_empty()
+            && other.left() >= self.left()
+            && other.right() <= self.right()
+            && other.top() >= self.top()
+            && other.bottom() <= self.bottom()
     }
 
     pub fn zero() -> Self {
@@ -28,26 +69,246 @@ impl Rect {
         }
     }
 
-    pub fn adjust_for_border(&mut self, border: (i32, i32)) {
-        self.x -= border.0;
-        self.width += border.0 * 2;
-        self.height += border.1;
+    pub fn adjust_for_border(&mut self, border: Size) {
+        self.x -= border.width;
+        self.width += border.width * 2;
+        self.height += border.height;
+    }
+
+    pub fn position(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    pub fn set_position(&mut self, point: Point) {
+        self.x = point.x;
+        self.y = point.y;
+    }
+
+    pub fn set_size(&mut self, size: Size) {
+        self.width = size.width;
+        self.height = size.height;
+    }
+
+    pub fn left_top(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+
+    pub fn right_top(&self) -> Point {
+        Point::new(self.x + self.width, self.y)
+    }
+
+    pub fn right_bottom(&self) -> Point {
+        Point::new(self.x + self.width, self.y + self.height)
+    }
+
+    pub fn left_bottom(&self) -> Point {
+        Point::new(self.x, self.y + self.height)
+    }
+
+    /// Inclusive left edge.
+    pub fn left(&self) -> i32 {
+        self.x
+    }
+
+    /// Inclusive top edge.
+    pub fn top(&self) -> i32 {
+        self.y
+    }
+
+    /// Exclusive right edge.
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    /// Exclusive bottom edge.
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    /// Center point, rounded towards the top left.
+    pub fn center(&self) -> Point {
+        Point::new(self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// Area in pixels, zero for empty rects.
+    pub fn area(&self) -> i64 {
+        if self.is_empty() {
+            return 0;
+        }
+        self.width as i64 * self.height as i64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    /// Overlapping part, `None` when they don't share any pixel.
+    /// Rects touching at an edge don't intersect.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let r = Rect::from_ltrb(
+            cmp::max(self.left(), other.left()),
+            cmp::max(self.top(), other.top()),
+            cmp::min(self.right(), other.right()),
+            cmp::min(self.bottom(), other.bottom()),
+        );
+
+        if r.is_empty() { None } else { Some(r) }
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.intersect(other).is_some()
+    }
+
+    /// Smallest rect containing both, empty rects are ignored.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if other.is_empty() {
+            return *self;
+        }
+        if self.is_empty() {
+            return *other;
+        }
+
+        Rect::from_ltrb(
+            cmp::min(self.left(), other.left()),
+            cmp::min(self.top(), other.top()),
+            cmp::max(self.right(), other.right()),
+            cmp::max(self.bottom(), other.bottom()),
+        )
+    }
+
+    /// Grow every edge outwards, negative values shrink.
+    pub fn inflate(&self, dx: i32, dy: i32) -> Rect {
+        Rect {
+            x:      self.x - dx,
+            y:      self.y - dy,
+            width:  self.width + dx * 2,
+            height: self.height + dy * 2,
+        }
+    }
+
+    /// Shrink every edge inwards, same as `inflate(-dx, -dy)`.
+    pub fn deflate(&self, dx: i32, dy: i32) -> Rect {
+        self.inflate(-dx, -dy)
+    }
+
+    pub fn translate(&self, offset: Offset) -> Rect {
+        Rect::from_point_size(self.position() + offset, self.size())
+    }
+
+    /// Move inside `bounds` with the least movement. When bigger than
+    /// `bounds` the size is cut down to it.
+    pub fn clamp_into(&self, bounds: &Rect) -> Rect {
+        let width = cmp::min(self.width, bounds.width);
+        let height = cmp::min(self.height, bounds.height);
+
+        Rect {
+            x: self.x.max(bounds.left()).min(bounds.right() - width),
+            y: self.y.max(bounds.top()).min(bounds.bottom() - height),
+            width,
+            height,
+        }
+    }
+
+    /// Gap between the rects on each axis, zero on an axis
+    /// where they overlap or touch.
+    pub fn gap(&self, other: &Rect) -> Size {
+        let dx = cmp::max(other.left() - self.right(), self.left() - other.right());
+        let dy = cmp::max(other.top() - self.bottom(), self.top() - other.bottom());
+
+        Size::new(cmp::max(dx, 0), cmp::max(dy, 0))
+    }
+
+    /// Distance from each edge to the point: (left, top, right, bottom),
+    /// positive when the point is inside of that edge.
+    pub fn edge_distances(&self, point: Point) -> (i32, i32, i32, i32) {
+        (
+            point.x - self.left(),
+            point.y - self.top(),
+            self.right() - point.x,
+            self.bottom() - point.y,
+        )
+    }
+}
+
+/// Space on each edge, e.g. margins or a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Insets {
+    pub left:   i32,
+    pub top:    i32,
+    pub right:  i32,
+    pub bottom: i32,
+}
+
+impl Insets {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Insets { left, top, right, bottom }
+    }
+
+    pub fn uniform(v: i32) -> Self {
+        Insets::new(v, v, v, v)
+    }
+
+    pub fn zero() -> Self {
+        Insets::default()
+    }
+
+    pub fn map(self, f: impl Fn(i32) -> i32) -> Self {
+        Insets::new(f(self.left), f(self.top), f(self.right), f(self.bottom))
     }
+}
 
-    pub fn left_top(&self) -> (i32, i32) {
-        (self.x, self.y)
+impl Rect {
+    /// Move each edge inwards by the insets, negative ones grow.
+    pub fn inset(&self, insets: Insets) -> Rect {
+        Rect::from_ltrb(
+            self.left() + insets.left,
+            self.top() + insets.top,
+            self.right() - insets.right,
+            self.bottom() - insets.bottom,
+        )
     }
+}
+
+/// One side of a rect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edge {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
 
-    pub fn right_top(&self) -> (i32, i32) {
-        (self.x + self.width, self.y)
+impl Edge {
+    pub const ALL: [Edge; 4] = [Edge::Left, Edge::Top, Edge::Right, Edge::Bottom];
+
+    // left and right are positions on the x axis
+    pub fn on_x_axis(self) -> bool {
+        matches!(self, Edge::Left | Edge::Right)
     }
 
-    pub fn right_bottom(&self) -> (i32, i32) {
-        (self.x + self.width, self.y + self.height)
+    pub fn opposite(self) -> Edge {
+        match self {
+            Edge::Left => Edge::Right,
+            Edge::Top => Edge::Bottom,
+            Edge::Right => Edge::Left,
+            Edge::Bottom => Edge::Top,
+        }
     }
+}
 
-    pub fn left_bottom(&self) -> (i32, i32) {
-        (self.x, self.y + self.height)
+impl Rect {
+    // position of the edge on its axis
+    pub fn edge(&self, edge: Edge) -> i32 {
+        match edge {
+            Edge::Left => self.left(),
+            Edge::Top => self.top(),
+            Edge::Right => self.right(),
+            Edge::Bottom => self.bottom(),
+        }
     }
 }
 
@@ -73,8 +334,176 @@ impl From<RECT> for Rect {
     }
 }
 
+impl From<Rect> for RECT {
+    fn from(rect: Rect) -> Self {
+        RECT {
+            left:   rect.left(),
+            top:    rect.top(),
+            right:  rect.right(),
+            bottom: rect.bottom(),
+        }
+    }
+}
+
 impl Default for Rect {
     fn default() -> Self {
         Rect::zero()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bindings::Windows::Win32::Foundation::RECT;
+
+    use crate::win2::point::{Offset, Point, Size};
+
+    use super::{Edge, Insets, Rect};
+
+    #[test]
+    fn test_edges() {
+        let r = Rect::new(10, 20, 30, 40);
+        assert_eq!((r.left(), r.top(), r.right(), r.bottom()), (10, 20, 40, 60));
+        assert_eq!(Rect::from_ltrb(10, 20, 40, 60), r);
+        assert_eq!(r.center(), Point::new(25, 40));
+        assert_eq!(r.position(), Point::new(10, 20));
+        assert_eq!(r.size(), Size::new(30, 40));
+        assert_eq!(Rect::from_point_size(r.position(), r.size()), r);
+        assert_eq!(r.area(), 1200);
+    }
+
+    #[test]
+    fn test_contains_point_exclusive() {
+        let r = Rect::new(0, 0, 10, 10);
+        assert!(r.contains_point(Point::new(0, 0)));
+        assert!(r.contains_point(Point::new(9, 9)));
+        assert!(!r.contains_point(Point::new(10, 5)));
+        assert!(!r.contains_point(Point::new(5, 10)));
+        assert!(!r.contains_point(Point::new(-1, 5)));
+        assert!(!Rect::zero().contains_point(Point::new(0, 0)));
+    }
+
+    #[test]
+    fn test_contains_rect() {
+        let r = Rect::new(0, 0, 10, 10);
+        assert!(r.contains_rect(r));
+        assert!(r.contains_rect(Rect::new(2, 2, 8, 8)));
+        assert!(!r.contains_rect(Rect::new(2, 2, 9, 8)));
+        assert!(!r.contains_rect(Rect::new(2, 2, 0, 0)));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(Rect::zero().is_empty());
+        assert!(Rect::new(0, 0, 10, 0).is_empty());
+        assert!(Rect::new(0, 0, -1, 10).is_empty());
+        assert!(!Rect::new(0, 0, 1, 1).is_empty());
+        assert_eq!(Rect::new(0, 0, -5, 10).area(), 0);
+    }
+
+    #[test]
+    fn test_intersect() {
+        let a = Rect::new(0, 0, 10, 10);
+        let cases = [
+            (Rect::new(5, 5, 10, 10), Some(Rect::new(5, 5, 5, 5))),
+            (Rect::new(2, 2, 4, 4), Some(Rect::new(2, 2, 4, 4))),
+            (Rect::new(-5, 3, 30, 2), Some(Rect::new(0, 3, 10, 2))),
+            // touching edges share no pixel
+            (Rect::new(10, 0, 10, 10), None),
+            (Rect::new(0, 10, 10, 10), None),
+            (Rect::new(20, 20, 5, 5), None),
+            (Rect::new(2, 2, 0, 0), None),
+        ];
+
+        for (b, expected) in cases.iter() {
+            assert_eq!(a.intersect(b), *expected, "{:?}", b);
+            assert_eq!(b.intersect(&a), *expected, "{:?}", b);
+            assert_eq!(a.intersects(b), expected.is_some());
+        }
+    }
+
+    #[test]
+    fn test_union() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert_eq!(a.union(&Rect::new(20, 5, 5, 20)), Rect::new(0, 0, 25, 25));
+        assert_eq!(a.union(&Rect::new(2, 2, 2, 2)), a);
+        assert_eq!(a.union(&Rect::new(100, 100, 0, 0)), a);
+        assert_eq!(Rect::zero().union(&a), a);
+    }
+
+    #[test]
+    fn test_inflate_deflate_translate() {
+        let r = Rect::new(10, 10, 20, 20);
+        assert_eq!(r.inflate(2, 3), Rect::new(8, 7, 24, 26));
+        assert_eq!(r.deflate(2, 3), Rect::new(12, 13, 16, 14));
+        assert_eq!(r.inflate(2, 3).deflate(2, 3), r);
+        assert_eq!(r.translate(Offset::new(-5, 7)), Rect::new(5, 17, 20, 20));
+    }
+
+    #[test]
+    fn test_inset() {
+        let r = Rect::new(10, 20, 100, 50);
+        assert_eq!(r.inset(Insets::new(1, 2, 3, 4)), Rect::new(11, 22, 96, 44));
+        assert_eq!(r.inset(Insets::uniform(5)), r.deflate(5, 5));
+        assert_eq!(r.inset(Insets::uniform(-5)), r.inflate(5, 5));
+        assert_eq!(r.inset(Insets::zero()), r);
+        assert!(r.inset(Insets::new(60, 0, 60, 0)).is_empty());
+        assert_eq!(Insets::new(1, 2, 3, 4).map(|v| v * 2), Insets::new(2, 4, 6, 8));
+    }
+
+    #[test]
+    fn test_edge_enum() {
+        let r = Rect::new(10, 20, 100, 50);
+        let cases = [
+            (Edge::Left, 10, true),
+            (Edge::Top, 20, false),
+            (Edge::Right, 110, true),
+            (Edge::Bottom, 70, false),
+        ];
+
+        for (edge, pos, x_axis) in cases.iter() {
+            assert_eq!(r.edge(*edge), *pos);
+            assert_eq!(edge.on_x_axis(), *x_axis);
+            assert_eq!(edge.opposite().on_x_axis(), *x_axis);
+            assert_eq!(edge.opposite().opposite(), *edge);
+        }
+    }
+
+    #[test]
+    fn test_clamp_into() {
+        let bounds = Rect::new(0, 0, 100, 100);
+        let cases = [
+            (Rect::new(10, 10, 20, 20), Rect::new(10, 10, 20, 20)),
+            (Rect::new(-10, 10, 20, 20), Rect::new(0, 10, 20, 20)),
+            (Rect::new(90, 95, 20, 20), Rect::new(80, 80, 20, 20)),
+            (Rect::new(-10, -10, 200, 50), Rect::new(0, 0, 100, 50)),
+            (Rect::new(50, 50, 200, 200), Rect::new(0, 0, 100, 100)),
+        ];
+
+        for (r, expected) in cases.iter() {
+            assert_eq!(r.clamp_into(&bounds), *expected, "{:?}", r);
+            assert!(bounds.contains_rect(r.clamp_into(&bounds)));
+        }
+    }
+
+    #[test]
+    fn test_gap_and_edge_distances() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert_eq!(a.gap(&Rect::new(15, 0, 10, 10)), Size::new(5, 0));
+        assert_eq!(a.gap(&Rect::new(-20, 30, 10, 10)), Size::new(10, 20));
+        assert_eq!(a.gap(&Rect::new(10, 10, 10, 10)), Size::zero());
+        assert_eq!(a.gap(&Rect::new(5, 5, 10, 10)), Size::zero());
+
+        assert_eq!(a.edge_distances(Point::new(3, 4)), (3, 4, 7, 6));
+        assert_eq!(a.edge_distances(Point::new(12, -1)), (12, -1, -2, 11));
+    }
+
+    #[test]
+    fn test_rect_round_trip() {
+        let raw = RECT { left: -10, top: 20, right: 30, bottom: 60 };
+        let r = Rect::from(raw);
+        assert_eq!(r, Rect::new(-10, 20, 40, 40));
+
+        let back: RECT = r.into();
+        assert_eq!((back.left, back.top, back.right, back.bottom), (-10, 20, 30, 60));
+    }
+}
@@ -0,0 +1,132 @@
+// pick top level windows by class, title and exe, e.g. to wait for
+// `WeChatMainWndForPC` of `WeChat.exe` to show up.
+
+use bindings::Windows::Win32::Foundation::HWND;
+
+use super::backend::WindowSystem;
+use super::window::Window;
+
+// all set fields must match, an empty selector matches any window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowSelector {
+    // exact window class
+    pub class: Option<String>,
+    // exact title
+    pub title: Option<String>,
+    // exe file name like `WeChat.exe`, or a full path,
+    // case insensitive
+    pub exe: Option<String>,
+}
+
+impl WindowSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_class(mut self, class: &str) -> Self {
+        self.class = Some(class.to_string());
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn with_exe(mut self, exe: &str) -> Self {
+        self.exe = Some(exe.to_string());
+        self
+    }
+
+    pub fn matches(&self, sys: &dyn WindowSystem, hwnd: HWND) -> bool {
+        if let Some(class) = &self.class {
+            if sys.class(hwnd).ok().as_ref() != Some(class) {
+                return false;
+            }
+        }
+
+        if let Some(title) = &self.title {
+            if sys.title(hwnd).as_ref() != Some(title) {
+                return false;
+            }
+        }
+
+        if let Some(exe) = &self.exe {
+            match sys.exe_path(hwnd) {
+                Ok(path) if exe_matches(exe, &path) => {},
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    // first matching top level window, in the order of `enum_windows`
+    pub fn find(&self, sys: &dyn WindowSystem) -> Option<Window> {
+        let mut found = None;
+        sys.enum_windows(&mut |w: Window| {
+            if self.matches(sys, w.hwnd) {
+                found = Some(w);
+                return false;
+            }
+            true
+        });
+
+        found
+    }
+}
+
+pub(crate) fn exe_matches(exe: &str, path: &str) -> bool {
+    let has_dir = exe.contains('\\') || exe.contains('/');
+    if has_dir {
+        return exe.eq_ignore_ascii_case(path);
+    }
+
+    // only the file name
+    let name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+    name.eq_ignore_ascii_case(exe)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::win2::{fake::{FakeDesktop, FakeWindow}, rect::Rect};
+
+    use super::WindowSelector;
+
+    #[test]
+    fn test_selector_matches() {
+        let desktop = FakeDesktop::new();
+        let login = desktop.create(FakeWindow {
+            exe_path: "C:\\Program Files\\Tencent\\WeChat\\WeChat.exe".to_string(),
+            ..FakeWindow::new("WeChatLoginWndForPC", "微信", Rect::new(0, 0, 100, 100))
+        });
+        let main = desktop.create(FakeWindow {
+            exe_path: "C:\\Program Files\\Tencent\\WeChat\\WeChat.exe".to_string(),
+            ..FakeWindow::new("WeChatMainWndForPC", "微信", Rect::new(0, 0, 100, 100))
+        });
+        let notepad = desktop.create(FakeWindow {
+            exe_path: "C:\\Windows\\notepad.exe".to_string(),
+            ..FakeWindow::new("Notepad", "", Rect::new(0, 0, 100, 100))
+        });
+
+        let wechat = WindowSelector::new().with_exe("wechat.EXE");
+        let cases = [
+            (WindowSelector::new(), [true, true, true]),
+            (wechat.clone(), [true, true, false]),
+            (wechat.clone().with_class("WeChatMainWndForPC"), [false, true, false]),
+            (WindowSelector::new().with_title("微信"), [true, true, false]),
+            (WindowSelector::new().with_exe("C:\\windows\\NOTEPAD.exe"), [false, false, true]),
+            (WindowSelector::new().with_exe("notepad"), [false, false, false]),
+            // untitled windows never match a title
+            (WindowSelector::new().with_title(""), [false, false, false]),
+        ];
+
+        for (selector, expected) in cases.iter() {
+            let got = [login, main, notepad].iter().map(|w| selector.matches(&desktop, w.hwnd)).collect::<Vec<_>>();
+            assert_eq!(got, expected.to_vec(), "{:?}", selector);
+        }
+
+        assert_eq!(wechat.find(&desktop), Some(login));
+        assert_eq!(WindowSelector::new().with_class("Nope").find(&desktop), None);
+    }
+}
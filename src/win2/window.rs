@@ -5,32 +5,54 @@ use std::convert::TryFrom;
 
 use bindings::Windows::Win32::Foundation::HANDLE;
 use bindings::Windows::Win32::Foundation::POINT;
+use bindings::Windows::Win32::Foundation::RECT;
+use bindings::Windows::Win32::Graphics::Gdi::ClientToScreen;
 use bindings::Windows::Win32::System::Threading::OpenProcess;
 use bindings::Windows::Win32::System::Threading::PROCESS_NAME_FORMAT;
 use bindings::Windows::Win32::System::Threading::PROCESS_QUERY_INFORMATION;
 use bindings::Windows::Win32::System::Threading::QueryFullProcessImageNameW;
+use bindings::Windows::Win32::UI::HiDpi::GetDpiForWindow;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::FindWindowW;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GA_ROOT;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GWL_EXSTYLE;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::GWLP_HWNDPARENT;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GW_HWNDPREV;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GW_OWNER;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GetWindow;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GetAncestor;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GetClientRect;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::GetWindowRect;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::HWND_NOTOPMOST;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::HWND_TOP;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::HWND_TOPMOST;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::IsZoomed;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::LWA_ALPHA;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::MoveWindow;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SET_WINDOW_POS_FLAGS;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SWP_NOACTIVATE;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::SWP_NOZORDER;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetCursorPos;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::SetLayeredWindowAttributes;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetParent;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetWindowLongA;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetWindowLongPtrA;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetWindowLongPtrW;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetWindowLongW;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::SetWindowPos;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::WS_EX_LAYERED;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::WS_EX_TOPMOST;
+use bindings::Windows::Win32::UI::WindowsAndMessaging::WS_EX_TRANSPARENT;
 use bindings::Windows::Win32::UI::WindowsAndMessaging::WindowFromPoint;
 use bindings::Windows::Win32::{Foundation::{
         HWND,
         PWSTR,
     }, Graphics::Dwm::{DWMWA_CLOAKED, DWMWINDOWATTRIBUTE, DWM_CLOAKED_APP, DWM_CLOAKED_INHERITED, DWM_CLOAKED_SHELL, DwmGetWindowAttribute}, System::Threading::AttachThreadInput, UI::{Input::KeyboardAndMouse::SetFocus, WindowsAndMessaging::{FindWindowA, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, RealGetWindowClassW, SHOW_WINDOW_CMD, SW_HIDE, SW_MAXIMIZE, SW_RESTORE, SW_SHOW, SetForegroundWindow, ShowWindow, WINDOWINFO}}};
 
+use super::dpi::{LogicalRect, PhysicalRect, ScaleFactor};
+use super::monitor::VirtualScreen;
+use super::point::{Point, Size};
 use super::rect::Rect;
 use super::window_enum::enum_windows;
 
@@ -265,6 +287,10 @@ impl Window {
         unsafe { IsIconic(hwnd) }.into()
     }
 
+    pub fn is_zoomed(hwnd: HWND) -> bool {
+        unsafe { IsZoomed(hwnd) }.into()
+    }
+
     pub fn get_exe_path(handle: HANDLE) -> Result<String> {
         let mut len = 260_u32;
         let mut path: Vec<u16> = vec![0; len as usize];
@@ -298,6 +324,26 @@ impl Window {
         Ok(())
     }
 
+    // move and put the window right above `above` in z order,
+    // it never becomes topmost.
+    pub fn set_window_rect_above(hwnd: HWND, rect: &Rect, above: HWND) -> Result<()> {
+        let prev = unsafe { GetWindow(above, GW_HWNDPREV) };
+        let style = unsafe { GetWindowLongPtrW(prev, GWL_EXSTYLE) } as u32;
+
+        // after a topmost one we would become topmost too,
+        // then `above` is the top of the normal windows.
+        let position = if prev.0 == 0 || style & WS_EX_TOPMOST.0 != 0 {
+            HWND_TOP
+        } else {
+            prev
+        };
+
+        if position == hwnd {
+            return Self::set_window_rect(hwnd, rect, position, SWP_NOACTIVATE | SWP_NOZORDER);
+        }
+        Self::set_window_rect(hwnd, rect, position, SWP_NOACTIVATE)
+    }
+
     pub fn move_window(hwnd: HWND, rect: &Rect, repaint: bool) -> Result<()> {
         unsafe {
             MoveWindow(hwnd, rect.x, rect.y, rect.width, rect.height, repaint)
@@ -313,15 +359,44 @@ impl Window {
         Ok(Rect::from(rect))
     }
 
-    fn set_cursor_pos(x: i32, y: i32) -> Result<()> {
-        unsafe { SetCursorPos(x, y) }.ok().end()
+    // client area in screen coordinates
+    pub fn get_client_rect(hwnd: HWND) -> Result<Rect> {
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(hwnd, &mut rect) }.ok().end()?;
+
+        let mut origin = POINT::default();
+        unsafe { ClientToScreen(hwnd, &mut origin) }.ok().end()?;
+
+        Ok(Rect::from(rect).translate(Point::from(origin) - Point::zero()))
     }
 
-    pub fn window_from_point(point: (i32, i32)) -> Result<HWND> {
-        let mut p = POINT::default();
-        p.x = point.0;
-        p.y = point.1;
-        let hwnd = unsafe { WindowFromPoint(p) };
+    // let mouse input go through to the windows below
+    pub fn set_window_click_through(hwnd: HWND, enable: bool) -> Result<()> {
+        let style = unsafe { GetWindowLongPtrW(hwnd, GWL_EXSTYLE) } as u32;
+        let flags = WS_EX_TRANSPARENT.0 | WS_EX_LAYERED.0;
+        let new_style = if enable { style | flags } else { style & !WS_EX_TRANSPARENT.0 };
+        if new_style == style {
+            return Ok(());
+        }
+
+        unsafe { SetWindowLongPtrW(hwnd, GWL_EXSTYLE, new_style as isize) };
+        if style & WS_EX_LAYERED.0 == 0 {
+            // a layered window is not drawn until it has attributes
+            unsafe { SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA) }.ok().end()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_window_dpi(hwnd: HWND) -> u32 {
+        unsafe { GetDpiForWindow(hwnd) }
+    }
+
+    fn set_cursor_pos(point: Point) -> Result<()> {
+        unsafe { SetCursorPos(point.x, point.y) }.ok().end()
+    }
+
+    pub fn window_from_point(point: Point) -> Result<HWND> {
+        let hwnd = unsafe { WindowFromPoint(POINT::from(point)) };
 
         Ok(hwnd)
     }
@@ -331,14 +406,14 @@ impl Window {
     }
 
     pub fn center_cursor_in_rect(rect: &Rect) -> Result<()> {
-        Self::set_cursor_pos(rect.x + (rect.width / 2), rect.y + (rect.height / 2))
+        Self::set_cursor_pos(rect.center())
     }
 
-    pub fn get_cursor_pos() -> Result<(i32, i32)> {
+    pub fn get_cursor_pos() -> Result<Point> {
         let mut p = POINT::default();
         unsafe { GetCursorPos(&mut p) };
 
-        Ok((p.x, p.y))
+        Ok(p.into())
     }
 
     pub fn set_window_parent(child: HWND, parent: HWND) -> Result<()> {
@@ -346,6 +421,15 @@ impl Window {
         Ok(())
     }
 
+    pub fn get_window_owner(hwnd: HWND) -> HWND {
+        unsafe { GetWindow(hwnd, GW_OWNER) }
+    }
+
+    // not a child window, owned ones are top level too
+    pub fn is_top_level_window(hwnd: HWND) -> bool {
+        unsafe { GetAncestor(hwnd, GA_ROOT) == hwnd }
+    }
+
     pub fn set_window_owner(child: HWND, owner: HWND) -> Result<()> {
         unsafe { SetWindowLongPtrA(child, GWLP_HWNDPARENT, owner.0); }
         Ok(())
@@ -408,10 +492,24 @@ impl Window {
         Self::get_exe_path(handle)
     }
 
-    // get rect
+    // get rect, in physical pixels
     pub fn rect(self) -> Result<Rect> {
         Self::get_window_rect(self.hwnd)
     }
+
+    // dpi of the monitor the window is on
+    pub fn dpi(self) -> u32 {
+        Self::get_window_dpi(self.hwnd)
+    }
+
+    pub fn scale_factor(self) -> ScaleFactor {
+        ScaleFactor::from_dpi(self.dpi())
+    }
+
+    // get rect in logical units
+    pub fn logical_rect(self) -> Result<LogicalRect> {
+        Ok(PhysicalRect(self.rect()?).to_logical(self.scale_factor()))
+    }
 }
 
 // implmenent other method for window
@@ -456,19 +554,28 @@ impl Window {
         let _ = Self::set_window_rect(self.hwnd, rect, position, SWP_NOACTIVATE);
     }
 
-    pub fn set_pos(self, point: (i32, i32)) {
+    // set rect but keep it inside the work area of nearest monitor
+    pub fn set_rect_in_work_area(self, rect: &Rect, top: bool) {
+        let rect = VirtualScreen::current().clamp_to_work_area(rect);
+        self.set_rect(&rect, top);
+    }
+
+    pub fn set_logical_rect(self, rect: &LogicalRect, top: bool) {
+        let physical = rect.to_physical(self.scale_factor());
+        self.set_rect(&physical.0, top);
+    }
+
+    pub fn set_pos(self, point: Point) {
         // get rect first
         let mut rect = self.rect().unwrap();
-        rect.x = point.0;
-        rect.y = point.1;
+        rect.set_position(point);
         let _ = Self::move_window(self.hwnd, &rect, true);
     }
 
-    pub fn set_size(self, size: (i32, i32)) {
+    pub fn set_size(self, size: Size) {
         // get rect first
         let mut rect = self.rect().unwrap();
-        rect.width = size.0;
-        rect.height = size.1;
+        rect.set_size(size);
         let _ = Self::move_window(self.hwnd, &rect, true);
     }
 
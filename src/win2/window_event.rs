@@ -1,7 +1,8 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicIsize, Ordering, AtomicBool};
+use std::fmt::{Display, Formatter};
+use std::io::Error;
+use std::sync::atomic::{Ordering, AtomicBool};
 use std::sync::{Arc, Mutex};
-use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
@@ -13,10 +14,14 @@ use bindings::Windows::Win32::UI::WindowsAndMessaging::{
     DispatchMessageW,
     PM_REMOVE,
     EVENT_MAX, EVENT_MIN, EVENT_OBJECT_CLOAKED, EVENT_OBJECT_DESTROY, EVENT_OBJECT_FOCUS, EVENT_OBJECT_HIDE, EVENT_OBJECT_SHOW, EVENT_OBJECT_UNCLOAKED, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND, EVENT_SYSTEM_MINIMIZESTART, EVENT_SYSTEM_MOVESIZEEND, EVENT_SYSTEM_MOVESIZESTART,
-    MSG, PeekMessageW, TranslateMessage, EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_CREATE};
-
-use crate::win2::message_loop::MessageLoop;
-
+    MSG, PeekMessageW, TranslateMessage, EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_CREATE,
+    EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_REORDER, EVENT_OBJECT_STATECHANGE, EVENT_OBJECT_VALUECHANGE,
+    EVENT_SYSTEM_CAPTUREEND, EVENT_SYSTEM_CAPTURESTART, EVENT_SYSTEM_DIALOGEND, EVENT_SYSTEM_DIALOGSTART, EVENT_SYSTEM_DRAGDROPEND, EVENT_SYSTEM_DRAGDROPSTART,
+    EVENT_SYSTEM_MENUEND, EVENT_SYSTEM_MENUPOPUPEND, EVENT_SYSTEM_MENUPOPUPSTART, EVENT_SYSTEM_MENUSTART};
+
+use super::backend::{EventChannel, Win32System, WindowSystem};
+use super::event_filter::EventFilter;
+use super::event_hub::{EventHub, Interest, RunId, SubscriberId};
 use super::error::Result;
 use super::window::Window;
 
@@ -24,7 +29,7 @@ lazy_static! {
     static ref WINDOWS_EVENT_CHANNEL: Arc<Mutex<(Sender<WinEvent>, Receiver<WinEvent>)>> =
         Arc::new(Mutex::new(unbounded()));
 
-    static ref EVENTS_CHANNELS: Arc<Mutex<HashMap<isize, Arc<Mutex<(Sender<WinEvent>, Receiver<WinEvent>)>>>>> = 
+    static ref EVENTS_CHANNELS: Arc<Mutex<HashMap<isize, EventChannel>>> = 
         Arc::new(Mutex::new(HashMap::new()));
 }
 
@@ -67,19 +72,66 @@ impl Window {
 pub struct WinEventListener {
     w: Window,
 
-    hook: AtomicIsize, // sotre the handle id
+    // platform backend to install the hook
+    sys: Arc<dyn WindowSystem>,
+
+    // the hooks are shared by all listeners of the backend
+    hub: Arc<EventHub>,
+    subscriber: Option<SubscriberId>,
+    // run by the hub thread after `start(false)`
+    run: Option<RunId>,
     exited: Arc<AtomicBool>, // exit the thead
 
 
-    ch: Arc<Mutex<(Sender<WinEvent>, Receiver<WinEvent>)>>,
+    ch: EventChannel,
 
-    // filter functions: all should be true
-    // filters: Arc<Mutex<Vec<Box<dyn FnMut(&WinEvent) -> bool + Send>>>>,
+    // filters: all should be true, checked when the hub routes the event
+    filters: Arc<Mutex<Vec<EventFilter>>>,
     // handle functions,
-    handlers: Arc<Mutex<HashMap<WinEventType, Vec<Box<dyn EventHandler + Send + Sync + 'static>>>>>,
+    handlers: Handlers,
+    next_id: u64,
     // handlers: Arc<Mutex<HashMap<WinEventType, Box<dyn EventHandler + Send + Sync + 'static>>>>,
 
-    thread: Option<JoinHandle<()>>, // thread for handle message
+    // called after each round of events, even if there was none
+    idle: IdleHandlers,
+    // of hooking the type of a handler added after `install`
+    error: Option<Error>,
+}
+
+type Handlers = Arc<Mutex<HashMap<WinEventType, Vec<Arc<Subscription>>>>>;
+type IdleHandlers = Arc<Mutex<Vec<Box<dyn FnMut() + Send + 'static>>>>;
+
+// returned by `on`, to remove the handler with `off`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+struct Subscription {
+    id: SubscriptionId,
+    // of the handler only, after the ones of the listener
+    filter: Option<EventFilter>,
+    // set by `off`, the event being handled may still hold it
+    removed: AtomicBool,
+    cb: Mutex<Box<dyn EventHandler + Send + Sync + 'static>>,
+}
+
+// removes handlers of a listener, can be moved into the handlers
+#[derive(Clone)]
+pub struct Subscriptions {
+    handlers: Handlers,
+}
+
+impl Subscriptions {
+    // false if there is no such handler (anymore)
+    pub fn off(&self, id: SubscriptionId) -> bool {
+        let mut handlers = self.handlers.lock().unwrap();
+        for v in handlers.values_mut() {
+            if let Some(i) = v.iter().position(|s| s.id == id) {
+                v.remove(i).removed.store(true, Ordering::SeqCst);
+                return true;
+            }
+        }
+        false
+    }
 }
 
 // pub struct ListenerWrapper(Arc<Mutex<WinEventListener>>);
@@ -87,141 +139,340 @@ pub struct WinEventListener {
 impl WinEventListener {
 
     pub fn new(w: Window) -> Self {
+        Self::with_system(w, Win32System::shared())
+    }
+
+    // create listener on other backend, e.g. the fake desktop
+    pub fn with_system(w: Window, sys: Arc<dyn WindowSystem>) -> Self {
         WinEventListener{
             w,
+            hub: EventHub::for_system(&sys),
+            sys,
 
-            hook: AtomicIsize::new(0),
+            subscriber: None,
+            run: None,
             exited: Arc::new(AtomicBool::new(false)),
 
             ch: Arc::new(Mutex::new(unbounded())),
 
-            // filters: Arc::new(Mutex::new(Vec::<_>::new())),
+            filters: Arc::new(Mutex::new(vec![])),
             handlers: Arc::new(Mutex::new(HashMap::new())),
-
-            thread: None,
+            next_id: 0,
+            idle: Arc::new(Mutex::new(Vec::new())),
+            error: None,
         }
     }
 
-    // on method to add event listener, evt type -> callback
-    pub fn on<Q>(&mut self, typ: WinEventType, cb: Q) -> &mut Self
+    // on method to add event listener, evt type -> callback,
+    // the id removes it again with `off`
+    pub fn on<Q>(&mut self, typ: WinEventType, cb: Q) -> SubscriptionId
     where
         Q: EventHandler + Send + Sync + 'static
     {
+        self.add_handler(typ, None, Box::new(cb))
+    }
+
+    // like `on`, only called for the events matching `filter`
+    pub fn on_filtered<Q>(&mut self, typ: WinEventType, filter: EventFilter, cb: Q) -> SubscriptionId
+    where
+        Q: EventHandler + Send + Sync + 'static
+    {
+        self.add_handler(typ, Some(filter), Box::new(cb))
+    }
+
+    fn add_handler(&mut self, typ: WinEventType, filter: Option<EventFilter>, cb: Box<dyn EventHandler + Send + Sync + 'static>) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+
         // TODO: add event listener by config
         self.handlers.lock().unwrap().entry(typ)
             .or_insert_with(Vec::new)
-            .push(Box::new(cb));
+            .push(Arc::new(Subscription { id, filter, removed: AtomicBool::new(false), cb: Mutex::new(cb) }));
+
+        // already hooked, get the events of the new type too,
+        // `install` tells if that failed
+        if let Some(sub) = self.subscriber {
+            if let Err(e) = self.hub.update(sub, self.interest()) {
+                self.error.get_or_insert(e);
+            }
+        }
+
+        id
+    }
+
+    // remove a handler added by `on`, the hooks stay until the
+    // listener is dropped. Handlers remove themselves with `subscriptions`.
+    pub fn off(&self, id: SubscriptionId) -> bool {
+        self.subscriptions().off(id)
+    }
 
+    pub fn subscriptions(&self) -> Subscriptions {
+        Subscriptions { handlers: self.handlers.clone() }
+    }
+
+    // drop the events not matching `filter` before any handler gets
+    // them, e.g. `EventFilter::WindowObject`
+    pub fn filter(&mut self, filter: EventFilter) -> &mut Self {
+        self.filters.lock().unwrap().push(filter);
         self
     }
 
-    pub fn start(&mut self, block: bool) -> Result<()> {
+    // add callback run by the loop after events are processed,
+    // for work that is due by time rather than by an event.
+    pub fn on_idle<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: FnMut() + Send + 'static
+    {
+        self.idle.lock().unwrap().push(Box::new(cb));
+        self
+    }
 
-        // install the win event hook function
-        let hook_handle = unsafe {
-            SetWinEventHook(
-                EVENT_MIN, 
-                EVENT_MAX, 
-                None, 
-                Some(thunk), 
-                0, 
-                0, 
-                0,
-            )
-        };
+    // subscribe to the hub only, events are queued
+    // until `pump` or the loop of `start` process them.
+    //
+    // Only the events of the registered types are hooked, of the
+    // process and thread of the window when there is one. Once
+    // installed, it fails when hooking for a handler added since did.
+    pub fn install(&mut self) -> Result<()> {
+        if self.subscriber.is_some() {
+            return match self.error.take() {
+                Some(e) => Err(e),
+                None => Ok(()),
+            };
+        }
 
-        // take the ch with hook_id?
-        println!("the event hook id {:?}", hook_handle);
+        let (tx, sys, filters) = (self.ch.lock().unwrap().0.clone(), self.sys.clone(), self.filters.clone());
+        let id = self.hub.subscribe_fn(self.interest(), Box::new(move |evt: &WinEvent| {
+            if filters.lock().unwrap().iter().all(|f| f.matches(&*sys, evt)) {
+                let _ = tx.send(*evt);
+            }
+        }))?;
+        self.subscriber = Some(id);
 
-        self.hook.store(hook_handle.0, Ordering::SeqCst);
+        Ok(())
+    }
 
-        // start the message loop thread
-        // and set the event hook handle for w32
-        // set to global static send
-        EVENTS_CHANNELS.lock().unwrap().insert(hook_handle.0, self.ch.clone());
+    // queue an event as if the hub routed it, e.g. one replayed
+    // from a trace. False if the filters of the listener drop it.
+    pub fn inject(&self, evt: WinEvent) -> bool {
+        if !self.filters.lock().unwrap().iter().all(|f| f.matches(&*self.sys, &evt)) {
+            return false;
+        }
+        self.ch.lock().unwrap().0.send(evt).is_ok()
+    }
 
-        let ch = self.ch.clone();
-        let _handlers = self.handlers.clone();
-        let _exited = self.exited.clone();
-        // let _filters = self.filters.clone();
-        let target_w = self.w;
-
-        let process = move || {
-            if let Ok(evt) = ch.lock().unwrap().1.try_recv() {
-                // filter and call with event type
-                // for f in _filters.lock().unwrap().into_iter() {
-                //     if !f(&evt) {
-                //         // if with false just ingore
-                //         return true;
-                //     }
-                // }
-
-                // hard code for window match
-                if target_w.is_valide() && evt.window != target_w {
-                    // return true;
-                    return;
-                }
+    // the events of the handlers, of the window if there is one
+    fn interest(&self) -> Interest {
+        let types = self.handlers.lock().unwrap().keys().copied().collect();
+        Interest::of_window(&*self.sys, self.w, types)
+    }
 
-                // call functions with type
-                match _handlers.lock().unwrap().get_mut(&evt.etype) {
-                    Some(v) => {
-                        for cb in v.into_iter() {
-                            cb.handle(&evt);
-                        }
-                    },
-                    None => {},
-                }
+    // process all queued events on current thread,
+    // returns how many events were taken.
+    pub fn pump(&self) -> usize {
+        // don't wait for the hub thread to route them
+        Self::process(&self.ch, &self.handlers, &self.idle, &*self.sys, &|| self.hub.dispatch())
+    }
 
-                // call functions all
-                match _handlers.lock().unwrap().get_mut(&WinEventType::All) {
-                    Some(v) => {
-                        for cb in v.into_iter() {
-                            cb.handle(&evt);
-                        }
-                    },
-                    None => {},
-                }
+    // process events until dropped, on the current thread when
+    // blocking, or else on the thread of the hub.
+    pub fn start(&mut self, block: bool) -> Result<()> {
 
-            }
+        // install the win event hook function
+        self.install()?;
 
-        };
+        let ch = self.ch.clone();
+        let _handlers = self.handlers.clone();
+        let _idle = self.idle.clone();
+        let _exited = self.exited.clone();
+        let sys = self.sys.clone();
 
         if block {
+            let hub = self.hub.clone();
             // start the message loop
-            MessageLoop::start(10, |_msg| {
-                process();
+            self.sys.run_loop(&mut || {
+                Self::process(&ch, &_handlers, &_idle, &*sys, &|| hub.dispatch());
 
-                true
+                !_exited.load(Ordering::SeqCst)
             });
-        } else {
-            // store the thread handle
-            self.thread = Some(thread::spawn(move || {
-                MessageLoop::start(10, |_msg| {
-                    process();
-    
-                    true
-                });
+        } else if self.run.is_none() {
+            self.run = Some(self.hub.run(move || {
+                // the hub routes before each run
+                Self::process(&ch, &_handlers, &_idle, &*sys, &|| 0);
             }));
         }
 
         Ok(())
     }
+
+    // `route` asks the hub for more, also the events caused by the handlers.
+    // Done when it routed none and none were queued meanwhile, e.g. by
+    // the hub thread routing while `route` waited for it.
+    fn process(ch: &EventChannel, handlers: &Handlers, idle: &IdleHandlers, sys: &dyn WindowSystem, route: &dyn Fn() -> usize) -> usize {
+        let mut count = 0;
+
+        loop {
+            let routed = route();
+            let mut taken = 0;
+            loop {
+                let next = ch.lock().unwrap().1.try_recv();
+                match next {
+                    Ok(evt) => Self::handle(handlers, sys, &evt),
+                    Err(_) => break,
+                }
+                taken += 1;
+            }
+            count += taken;
+
+            if routed == 0 && taken == 0 {
+                break;
+            }
+        }
+
+        for cb in idle.lock().unwrap().iter_mut() {
+            cb();
+        }
+
+        count
+    }
+
+    // call functions with type, then functions all. The map is not
+    // locked while they run, they may add or remove handlers.
+    fn handle(handlers: &Handlers, sys: &dyn WindowSystem, evt: &WinEvent) {
+        let matched = {
+            let handlers = handlers.lock().unwrap();
+            [Some(evt.etype), evt.etype.group(), Some(WinEventType::All)].iter()
+                .flatten()
+                .filter_map(|t| handlers.get(t))
+                .flat_map(|v| v.iter().cloned())
+                .collect::<Vec<_>>()
+        };
+        for s in matched {
+            if s.removed.load(Ordering::SeqCst) {
+                continue;
+            }
+            let wanted = match &s.filter {
+                Some(f) => f.matches(sys, evt),
+                None => true,
+            };
+            if wanted {
+                s.cb.lock().unwrap().handle(evt);
+            }
+        }
+    }
 }
 
 impl Drop for WinEventListener {
     fn drop(&mut self) {
-        // unhook the window
-        let hid = self.hook.load(Ordering::SeqCst);
-        unsafe {
-            UnhookWinEvent(HWINEVENTHOOK(hid));
+        // stop being run and routed to, the hub unhooks
+        // what no other listener needs
+        if let Some(run) = self.run.take() {
+            self.hub.stop(run);
+        }
+        if let Some(id) = self.subscriber.take() {
+            self.hub.unsubscribe(id);
         }
-        println!("remove the hook {}", hid);
 
         // exit thread
         self.exited.store(true, Ordering::SeqCst);
     }
 }
 
+// what a hook gets: the raw events from `min` to `max` of the
+// process and thread, 0 is any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HookScope {
+    pub min: u32,
+    pub max: u32,
+    pub pid: u32,
+    pub tid: u32,
+}
+
+impl HookScope {
+    // every event of every process
+    pub fn all() -> Self {
+        Self { min: EVENT_MIN, max: EVENT_MAX, pid: 0, tid: 0 }
+    }
+
+    pub fn contains(&self, event: u32, w: &Window) -> bool {
+        (self.min..=self.max).contains(&event)
+            && (self.pid == 0 || self.pid == w.pid)
+            && (self.tid == 0 || self.tid == w.tid)
+    }
+}
+
+// the fewest ranges of raw event ids covering the types,
+// ids next to each other share a range.
+pub fn event_ranges(types: &[WinEventType]) -> Vec<(u32, u32)> {
+    let mut events = vec![];
+    for typ in types {
+        match typ.raw_events() {
+            Some(raw) => events.extend_from_slice(raw),
+            None => return vec![(EVENT_MIN, EVENT_MAX)],
+        }
+    }
+    events.sort_unstable();
+    events.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for evt in events {
+        match ranges.last_mut() {
+            Some((_, max)) if *max + 1 == evt => *max = evt,
+            _ => ranges.push((evt, evt)),
+        }
+    }
+    ranges
+}
+
+// parts of the needed ranges not in any of the ones we have
+pub(crate) fn missing_ranges(needed: &[(u32, u32)], have: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut missing = needed.to_vec();
+    for &(hmin, hmax) in have {
+        missing = missing.into_iter().flat_map(|(min, max)| {
+            let mut rest = vec![];
+            if min < hmin {
+                rest.push((min, max.min(hmin - 1)));
+            }
+            if max > hmax {
+                rest.push((min.max(hmax + 1), max));
+            }
+            rest
+        }).collect();
+    }
+    missing
+}
+
+// install the win32 event hook and register the channel for it
+pub(crate) fn set_win_event_hook(ch: EventChannel, scope: HookScope) -> Result<isize> {
+    let hook_handle = unsafe {
+        SetWinEventHook(
+            scope.min,
+            scope.max,
+            None, 
+            Some(thunk), 
+            scope.pid,
+            scope.tid,
+            0,
+        )
+    };
+
+    if hook_handle.0 == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // set to global static send
+    EVENTS_CHANNELS.lock().unwrap().insert(hook_handle.0, ch);
+
+    Ok(hook_handle.0)
+}
+
+pub(crate) fn unhook_win_event(hook: isize) {
+    unsafe {
+        UnhookWinEvent(HWINEVENTHOOK(hook));
+    }
+    EVENTS_CHANNELS.lock().unwrap().remove(&hook);
+}
+
 // global hook send event to static global queue
 // global single thread process the event
 // send to each single tread queue
@@ -242,6 +493,7 @@ unsafe extern "system" fn thunk(
     evt.raw_id_child = _id_child;
     evt.raw_id_object = _id_object;
     evt.raw_id_thread = _id_event_thread;
+    evt.raw_event_time = _dwms_event_time;
 
     // TODO: add filter at here ingore windows not match???
 
@@ -266,21 +518,110 @@ unsafe extern "system" fn thunk(
 }
 
 
+// one variant per raw event worth telling apart, and some aliases
+// of related ones, e.g. `Hide` for hidden, cloaked and minimized.
 #[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
 pub enum WinEventType {
     Destroy,
     Create,
-    Show,
-    Hide,
-    FocusChange,
+    Shown,
+    Hidden,
+    Cloaked,
+    Uncloaked,
+    Minimized,
+    // minimize ended
+    Restored,
+    Focus,
+    Foreground,
     MoveResizeStart,
     MoveResizeEnd,
     LocationChange,
+    NameChange,
+    // z order of the children changed
+    Reorder,
+    StateChange,
+    ValueChange,
+    DragDropStart,
+    DragDropEnd,
+    MenuStart,
+    MenuEnd,
+    MenuPopupStart,
+    MenuPopupEnd,
+    CaptureStart,
+    CaptureEnd,
+    DialogStart,
+    DialogEnd,
+
+    // aliases, never the type of an event
+    Show,
+    Hide,
+    FocusChange,
+    Menu,
 
     Unknown,
     All,
 }
 
+impl WinEventType {
+
+    // the alias covering the type
+    pub fn group(self) -> Option<WinEventType> {
+        Some(match self {
+            Self::Shown | Self::Uncloaked | Self::Restored => Self::Show,
+            Self::Hidden | Self::Cloaked | Self::Minimized => Self::Hide,
+            Self::Focus | Self::Foreground => Self::FocusChange,
+            Self::MenuStart | Self::MenuEnd | Self::MenuPopupStart | Self::MenuPopupEnd => Self::Menu,
+            _ => return None,
+        })
+    }
+
+    // handlers of `self` get events of type `other`
+    pub fn covers(self, other: WinEventType) -> bool {
+        self == other || self == Self::All || other.group() == Some(self)
+    }
+
+    // raw event ids of the type, `None` is any
+    pub fn raw_events(self) -> Option<&'static [u32]> {
+        Some(match self {
+            Self::Destroy => &[EVENT_OBJECT_DESTROY],
+            Self::Create => &[EVENT_OBJECT_CREATE],
+            Self::Shown => &[EVENT_OBJECT_SHOW],
+            Self::Hidden => &[EVENT_OBJECT_HIDE],
+            Self::Cloaked => &[EVENT_OBJECT_CLOAKED],
+            Self::Uncloaked => &[EVENT_OBJECT_UNCLOAKED],
+            Self::Minimized => &[EVENT_SYSTEM_MINIMIZESTART],
+            Self::Restored => &[EVENT_SYSTEM_MINIMIZEEND],
+            Self::Focus => &[EVENT_OBJECT_FOCUS],
+            Self::Foreground => &[EVENT_SYSTEM_FOREGROUND],
+            Self::MoveResizeStart => &[EVENT_SYSTEM_MOVESIZESTART],
+            Self::MoveResizeEnd => &[EVENT_SYSTEM_MOVESIZEEND],
+            Self::LocationChange => &[EVENT_OBJECT_LOCATIONCHANGE],
+            Self::NameChange => &[EVENT_OBJECT_NAMECHANGE],
+            Self::Reorder => &[EVENT_OBJECT_REORDER],
+            Self::StateChange => &[EVENT_OBJECT_STATECHANGE],
+            Self::ValueChange => &[EVENT_OBJECT_VALUECHANGE],
+            Self::DragDropStart => &[EVENT_SYSTEM_DRAGDROPSTART],
+            Self::DragDropEnd => &[EVENT_SYSTEM_DRAGDROPEND],
+            Self::MenuStart => &[EVENT_SYSTEM_MENUSTART],
+            Self::MenuEnd => &[EVENT_SYSTEM_MENUEND],
+            Self::MenuPopupStart => &[EVENT_SYSTEM_MENUPOPUPSTART],
+            Self::MenuPopupEnd => &[EVENT_SYSTEM_MENUPOPUPEND],
+            Self::CaptureStart => &[EVENT_SYSTEM_CAPTURESTART],
+            Self::CaptureEnd => &[EVENT_SYSTEM_CAPTUREEND],
+            Self::DialogStart => &[EVENT_SYSTEM_DIALOGSTART],
+            Self::DialogEnd => &[EVENT_SYSTEM_DIALOGEND],
+
+            Self::Show => &[EVENT_OBJECT_SHOW, EVENT_OBJECT_UNCLOAKED, EVENT_SYSTEM_MINIMIZEEND],
+            Self::Hide => &[EVENT_OBJECT_CLOAKED, EVENT_OBJECT_HIDE, EVENT_SYSTEM_MINIMIZESTART],
+            Self::FocusChange => &[EVENT_OBJECT_FOCUS, EVENT_SYSTEM_FOREGROUND],
+            Self::Menu => &[EVENT_SYSTEM_MENUSTART, EVENT_SYSTEM_MENUEND, EVENT_SYSTEM_MENUPOPUPSTART, EVENT_SYSTEM_MENUPOPUPEND],
+
+            Self::Unknown => &[],
+            Self::All => return None,
+        })
+    }
+}
+
 impl From<u32> for WinEventType {
 
     fn from(event: u32) -> Self {
@@ -289,27 +630,128 @@ impl From<u32> for WinEventType {
             EVENT_OBJECT_DESTROY => Self::Destroy,
             EVENT_OBJECT_CREATE => Self::Create,
 
-            EVENT_OBJECT_CLOAKED
-            | EVENT_OBJECT_HIDE
-            | EVENT_SYSTEM_MINIMIZESTART => Self::Hide,
-
-            EVENT_OBJECT_SHOW
-            | EVENT_OBJECT_UNCLOAKED
-            | EVENT_SYSTEM_MINIMIZEEND => Self::Show,
+            EVENT_OBJECT_SHOW => Self::Shown,
+            EVENT_OBJECT_HIDE => Self::Hidden,
+            EVENT_OBJECT_CLOAKED => Self::Cloaked,
+            EVENT_OBJECT_UNCLOAKED => Self::Uncloaked,
+            EVENT_SYSTEM_MINIMIZESTART => Self::Minimized,
+            EVENT_SYSTEM_MINIMIZEEND => Self::Restored,
 
-            EVENT_OBJECT_FOCUS
-            | EVENT_SYSTEM_FOREGROUND => Self::FocusChange,
+            EVENT_OBJECT_FOCUS => Self::Focus,
+            EVENT_SYSTEM_FOREGROUND => Self::Foreground,
 
             EVENT_SYSTEM_MOVESIZESTART => Self::MoveResizeStart,
             EVENT_SYSTEM_MOVESIZEEND => Self::MoveResizeEnd,
 
             EVENT_OBJECT_LOCATIONCHANGE => Self::LocationChange,
+            EVENT_OBJECT_NAMECHANGE => Self::NameChange,
+            EVENT_OBJECT_REORDER => Self::Reorder,
+            EVENT_OBJECT_STATECHANGE => Self::StateChange,
+            EVENT_OBJECT_VALUECHANGE => Self::ValueChange,
+
+            EVENT_SYSTEM_DRAGDROPSTART => Self::DragDropStart,
+            EVENT_SYSTEM_DRAGDROPEND => Self::DragDropEnd,
+            EVENT_SYSTEM_MENUSTART => Self::MenuStart,
+            EVENT_SYSTEM_MENUEND => Self::MenuEnd,
+            EVENT_SYSTEM_MENUPOPUPSTART => Self::MenuPopupStart,
+            EVENT_SYSTEM_MENUPOPUPEND => Self::MenuPopupEnd,
+            EVENT_SYSTEM_CAPTURESTART => Self::CaptureStart,
+            EVENT_SYSTEM_CAPTUREEND => Self::CaptureEnd,
+            EVENT_SYSTEM_DIALOGSTART => Self::DialogStart,
+            EVENT_SYSTEM_DIALOGEND => Self::DialogEnd,
             
             _ => Self::Unknown,
         }
     }
 }
 
+// names of the raw events, for logs
+mod names {
+    use bindings::Windows::Win32::UI::WindowsAndMessaging::*;
+
+    macro_rules! event_names {
+        ($($name:ident),* $(,)?) => {
+            pub const EVENT_NAMES: &[(u32, &str)] = &[$(($name, stringify!($name))),*];
+        };
+    }
+
+    event_names!(
+        EVENT_SYSTEM_SOUND,
+        EVENT_SYSTEM_ALERT,
+        EVENT_SYSTEM_FOREGROUND,
+        EVENT_SYSTEM_MENUSTART,
+        EVENT_SYSTEM_MENUEND,
+        EVENT_SYSTEM_MENUPOPUPSTART,
+        EVENT_SYSTEM_MENUPOPUPEND,
+        EVENT_SYSTEM_CAPTURESTART,
+        EVENT_SYSTEM_CAPTUREEND,
+        EVENT_SYSTEM_MOVESIZESTART,
+        EVENT_SYSTEM_MOVESIZEEND,
+        EVENT_SYSTEM_CONTEXTHELPSTART,
+        EVENT_SYSTEM_CONTEXTHELPEND,
+        EVENT_SYSTEM_DRAGDROPSTART,
+        EVENT_SYSTEM_DRAGDROPEND,
+        EVENT_SYSTEM_DIALOGSTART,
+        EVENT_SYSTEM_DIALOGEND,
+        EVENT_SYSTEM_SCROLLINGSTART,
+        EVENT_SYSTEM_SCROLLINGEND,
+        EVENT_SYSTEM_SWITCHSTART,
+        EVENT_SYSTEM_SWITCHEND,
+        EVENT_SYSTEM_MINIMIZESTART,
+        EVENT_SYSTEM_MINIMIZEEND,
+        EVENT_SYSTEM_DESKTOPSWITCH,
+        EVENT_SYSTEM_SWITCHER_APPGRABBED,
+        EVENT_SYSTEM_SWITCHER_APPOVERTARGET,
+        EVENT_SYSTEM_SWITCHER_APPDROPPED,
+        EVENT_SYSTEM_SWITCHER_CANCELLED,
+        EVENT_SYSTEM_IME_KEY_NOTIFICATION,
+        EVENT_OBJECT_CREATE,
+        EVENT_OBJECT_DESTROY,
+        EVENT_OBJECT_SHOW,
+        EVENT_OBJECT_HIDE,
+        EVENT_OBJECT_REORDER,
+        EVENT_OBJECT_FOCUS,
+        EVENT_OBJECT_SELECTION,
+        EVENT_OBJECT_SELECTIONADD,
+        EVENT_OBJECT_SELECTIONREMOVE,
+        EVENT_OBJECT_SELECTIONWITHIN,
+        EVENT_OBJECT_STATECHANGE,
+        EVENT_OBJECT_LOCATIONCHANGE,
+        EVENT_OBJECT_NAMECHANGE,
+        EVENT_OBJECT_DESCRIPTIONCHANGE,
+        EVENT_OBJECT_VALUECHANGE,
+        EVENT_OBJECT_PARENTCHANGE,
+        EVENT_OBJECT_HELPCHANGE,
+        EVENT_OBJECT_DEFACTIONCHANGE,
+        EVENT_OBJECT_ACCELERATORCHANGE,
+        EVENT_OBJECT_INVOKED,
+        EVENT_OBJECT_TEXTSELECTIONCHANGED,
+        EVENT_OBJECT_CONTENTSCROLLED,
+        EVENT_SYSTEM_ARRANGMENTPREVIEW,
+        EVENT_OBJECT_CLOAKED,
+        EVENT_OBJECT_UNCLOAKED,
+        EVENT_OBJECT_LIVEREGIONCHANGED,
+        EVENT_OBJECT_HOSTEDOBJECTSINVALIDATED,
+        EVENT_OBJECT_DRAGSTART,
+        EVENT_OBJECT_DRAGCANCEL,
+        EVENT_OBJECT_DRAGCOMPLETE,
+        EVENT_OBJECT_DRAGENTER,
+        EVENT_OBJECT_DRAGLEAVE,
+        EVENT_OBJECT_DRAGDROPPED,
+        EVENT_OBJECT_IME_SHOW,
+        EVENT_OBJECT_IME_HIDE,
+        EVENT_OBJECT_IME_CHANGE,
+        EVENT_OBJECT_TEXTEDIT_CONVERSIONTARGETCHANGED,
+    );
+}
+
+pub use names::EVENT_NAMES;
+
+// like `EVENT_OBJECT_SHOW`, `None` for ids of no known event
+pub fn event_name(event: u32) -> Option<&'static str> {
+    EVENT_NAMES.iter().find(|(id, _)| *id == event).map(|(_, name)| *name)
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct WinEvent {
     pub etype: WinEventType,
@@ -321,29 +763,276 @@ pub struct WinEvent {
     pub raw_id_child: i32,
     pub raw_id_object: i32,
     pub raw_id_thread: u32,
+    // when it happened, ms since the system started
+    pub raw_event_time: u32,
 }
 
 impl WinEvent {
 
     // create the event from args
     pub fn new(hook_handle: HWINEVENTHOOK, event: u32, hwnd: HWND) -> Self {
+        Self::with_window(hook_handle, event, hwnd.into())
+    }
+
+    // create the event for a known window, no lookup of process
+    pub fn with_window(hook_handle: HWINEVENTHOOK, event: u32, window: Window) -> Self {
         Self{
             etype: event.into(),
-            window: hwnd.into(),
+            window,
 
             hook_handle,
             raw_event: event,
             raw_id_child: 0,
             raw_id_object: 0,
             raw_id_thread: 0,
+            raw_event_time: 0,
         }
     }
 }
 
+impl Display for WinEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match event_name(self.raw_event) {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "{:#x}", self.raw_event)?,
+        }
+        write!(f, " (hwnd: {}, object: {}, child: {})", self.window.hwnd.0, self.raw_id_object, self.raw_id_child)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::win2::{window::*, window_event::{WinEventType, WinEvent}, message_loop::MessageLoop};
+    use std::sync::{Arc, Mutex};
+
+    use bindings::Windows::Win32::UI::WindowsAndMessaging::{EVENT_MAX, EVENT_MIN, EVENT_OBJECT_CLOAKED, EVENT_OBJECT_CREATE, EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_SELECTION, EVENT_OBJECT_SHOW, EVENT_SYSTEM_MINIMIZESTART, EVENT_SYSTEM_SOUND};
+
+    use crate::win2::{window::*, window_event::{WinEventType, WinEvent, WinEventListener}, message_loop::MessageLoop};
+    use crate::win2::{backend::WindowSystem, fake::{FakeDesktop, FakeWindow}, rect::Rect};
+
+    use crate::win2::event_filter::EventFilter;
+
+    use super::{event_name, event_ranges, missing_ranges, HookScope, EVENT_NAMES};
+
+    type Ranges = &'static [(u32, u32)];
+
+    #[test]
+    fn test_raw_events_round_trip() {
+        use WinEventType::*;
+
+        let types = [
+            Destroy, Create, Shown, Hidden, Cloaked, Uncloaked, Minimized, Restored, Focus, Foreground,
+            MoveResizeStart, MoveResizeEnd, LocationChange, NameChange, Reorder, StateChange, ValueChange,
+            DragDropStart, DragDropEnd, MenuStart, MenuEnd, MenuPopupStart, MenuPopupEnd,
+            CaptureStart, CaptureEnd, DialogStart, DialogEnd,
+        ];
+        for typ in types.iter() {
+            let raw = typ.raw_events().unwrap();
+            assert_eq!(raw.len(), 1, "{:?}", typ);
+            assert_eq!(WinEventType::from(raw[0]), *typ, "{:#x}", raw[0]);
+        }
+
+        // aliases are made of the types they cover
+        for alias in [Show, Hide, FocusChange, Menu].iter() {
+            let raw = alias.raw_events().unwrap();
+            assert!(raw.len() > 1, "{:?}", alias);
+            for evt in raw.iter() {
+                let typ = WinEventType::from(*evt);
+                assert_eq!(typ.group(), Some(*alias), "{:#x}", evt);
+                assert!(alias.covers(typ) && All.covers(typ) && typ.covers(typ));
+            }
+            assert_eq!(types.iter().filter(|t| alias.covers(**t)).count(), raw.len());
+        }
+
+        assert!(!Hide.covers(Shown));
+        assert!(!Minimized.covers(Hide));
+        assert_eq!(Unknown.raw_events(), Some(&[][..]));
+        assert_eq!(All.raw_events(), None);
+        assert_eq!(WinEventType::from(EVENT_OBJECT_SELECTION), Unknown);
+    }
+
+    #[test]
+    fn test_event_names() {
+        let cases = [
+            (EVENT_SYSTEM_SOUND, Some("EVENT_SYSTEM_SOUND")),
+            (EVENT_SYSTEM_MINIMIZESTART, Some("EVENT_SYSTEM_MINIMIZESTART")),
+            (EVENT_OBJECT_NAMECHANGE, Some("EVENT_OBJECT_NAMECHANGE")),
+            (EVENT_OBJECT_CLOAKED, Some("EVENT_OBJECT_CLOAKED")),
+            (0x8030, Some("EVENT_OBJECT_TEXTEDIT_CONVERSIONTARGETCHANGED")),
+            // range bounds, not events
+            (EVENT_MIN - 1, None),
+            (EVENT_MAX, None),
+            (0x80FF, None),
+        ];
+        for (event, name) in cases.iter() {
+            assert_eq!(event_name(*event), *name, "{:#x}", event);
+        }
+
+        // ids and names are unique, every typed event has a name
+        for (i, (id, name)) in EVENT_NAMES.iter().enumerate() {
+            assert!(name.starts_with("EVENT_"), "{}", name);
+            assert!(EVENT_NAMES[i + 1..].iter().all(|(other, n)| other != id && n != name), "{}", name);
+        }
+        for (id, _) in EVENT_NAMES.iter() {
+            if let Some(raw) = WinEventType::from(*id).raw_events() {
+                assert!(raw.iter().all(|r| event_name(*r).is_some()));
+            }
+        }
+
+        let mut evt = WinEvent::with_window(Default::default(), EVENT_OBJECT_SHOW, Window::default());
+        evt.raw_id_object = -4;
+        assert_eq!(evt.to_string(), "EVENT_OBJECT_SHOW (hwnd: 0, object: -4, child: 0)");
+        assert_eq!(WinEvent::with_window(Default::default(), 0x4e00, Window::default()).to_string(), "0x4e00 (hwnd: 0, object: 0, child: 0)");
+    }
+
+    #[test]
+    fn test_event_ranges() {
+        use WinEventType::*;
+
+        let cases: &[(&[WinEventType], Ranges)] = &[
+            (&[], &[]),
+            (&[LocationChange], &[(0x800B, 0x800B)]),
+            // next to each other share one range
+            (&[MoveResizeStart, MoveResizeEnd], &[(0xA, 0xB)]),
+            (&[Create, Destroy, LocationChange, Create], &[(0x8000, 0x8001), (0x800B, 0x800B)]),
+            (&[Show, Hide], &[(0x16, 0x17), (0x8002, 0x8003), (0x8017, 0x8018)]),
+            (&[FocusChange, Unknown], &[(0x3, 0x3), (0x8005, 0x8005)]),
+            (&[LocationChange, All], &[(EVENT_MIN, EVENT_MAX)]),
+        ];
+
+        for (types, expected) in cases.iter() {
+            assert_eq!(event_ranges(types), expected.to_vec(), "{:?}", types);
+        }
+    }
+
+    #[test]
+    fn test_missing_ranges() {
+        let cases: &[(Ranges, Ranges, Ranges)] = &[
+            (&[(1, 10)], &[], &[(1, 10)]),
+            (&[(1, 10)], &[(1, 10)], &[]),
+            (&[(1, 10)], &[(3, 4), (8, 20)], &[(1, 2), (5, 7)]),
+            (&[(3, 3), (8, 9)], &[(1, 5)], &[(8, 9)]),
+            (&[(EVENT_MIN, EVENT_MAX)], &[(0xA, 0xB)], &[(EVENT_MIN, 0x9), (0xC, EVENT_MAX)]),
+        ];
+
+        for (needed, have, expected) in cases.iter() {
+            assert_eq!(missing_ranges(needed, have), expected.to_vec(), "{:?} - {:?}", needed, have);
+        }
+    }
+
+    #[test]
+    fn test_listener_hooks_scope() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow { pid: 7, tid: 8, ..FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)) });
+        let other = desktop.create(FakeWindow { pid: 9, tid: 9, ..FakeWindow::new("Other", "other", Rect::new(0, 0, 100, 100)) });
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (_moved, _shown) = (seen.clone(), seen.clone());
+
+        let mut listener = WinEventListener::with_system(target, desktop.clone());
+        listener.on(WinEventType::LocationChange, move |evt: &WinEvent| _moved.lock().unwrap().push(evt.etype));
+        listener.install().unwrap();
+
+        // only location changes of the process and thread of target
+        assert_eq!(desktop.hooks(), vec![
+            HookScope { min: EVENT_OBJECT_LOCATIONCHANGE, max: EVENT_OBJECT_LOCATIONCHANGE, pid: 7, tid: 8 },
+        ]);
+
+        desktop.move_to(other.hwnd, Rect::new(10, 10, 100, 100));
+        desktop.set_visible(target.hwnd, false);
+        desktop.set_visible(target.hwnd, true);
+        assert_eq!(listener.pump(), 0);
+
+        // added after install, hooked too
+        listener.on(WinEventType::Show, move |evt: &WinEvent| _shown.lock().unwrap().push(evt.etype));
+        listener.on(WinEventType::LocationChange, |_: &WinEvent| {});
+        assert_eq!(desktop.hooks().len(), 4);
+
+        desktop.set_visible(target.hwnd, false);
+        desktop.set_visible(target.hwnd, true);
+        desktop.move_to(target.hwnd, Rect::new(10, 10, 100, 100));
+        assert_eq!(listener.pump(), 2);
+        assert_eq!(*seen.lock().unwrap(), vec![WinEventType::Shown, WinEventType::LocationChange]);
+
+        // the desktop listener gets all processes
+        let mut all = WinEventListener::with_system(Window::default(), desktop.clone());
+        all.on(WinEventType::Create, |_: &WinEvent| {});
+        all.install().unwrap();
+        assert_eq!(desktop.hooks().last(), Some(&HookScope { min: EVENT_OBJECT_CREATE, max: EVENT_OBJECT_CREATE, pid: 0, tid: 0 }));
+
+        // hooking a type added later failed, told by install
+        desktop.fail_hooks(true);
+        all.on(WinEventType::Destroy, |_: &WinEvent| {});
+        assert!(all.install().is_err());
+        assert!(all.install().is_ok());
+        desktop.fail_hooks(false);
+
+        drop(listener);
+        drop(all);
+        assert_eq!(desktop.hooks(), vec![]);
+    }
+
+    #[test]
+    fn test_listener_off() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(0, 0, 100, 100)));
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (_kept, _removed, _once) = (seen.clone(), seen.clone(), seen.clone());
+
+        let mut listener = WinEventListener::with_system(target, desktop.clone());
+        listener.on(WinEventType::LocationChange, move |_: &WinEvent| _kept.lock().unwrap().push("kept"));
+        let removed = listener.on(WinEventType::LocationChange, move |_: &WinEvent| _removed.lock().unwrap().push("removed"));
+
+        // removes itself from inside the callback
+        let me = Arc::new(Mutex::new(None));
+        let (_me, subscriptions) = (me.clone(), listener.subscriptions());
+        let once = listener.on(WinEventType::All, move |_: &WinEvent| {
+            _once.lock().unwrap().push("once");
+            if let Some(id) = *_me.lock().unwrap() {
+                assert!(subscriptions.off(id));
+            }
+        });
+        *me.lock().unwrap() = Some(once);
+        listener.install().unwrap();
+
+        assert!(listener.off(removed));
+        assert!(!listener.off(removed));
+
+        desktop.move_to(target.hwnd, Rect::new(10, 10, 100, 100));
+        desktop.move_to(target.hwnd, Rect::new(20, 20, 100, 100));
+        assert_eq!(listener.pump(), 2);
+        assert_eq!(*seen.lock().unwrap(), vec!["kept", "once", "kept"]);
+        assert!(!listener.off(once));
+    }
+
+    #[test]
+    fn test_listener_filters() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let login = desktop.create(FakeWindow::new("WeChatLoginWndForPC", "微信", Rect::new(0, 0, 100, 100)));
+        let main = desktop.create(FakeWindow::new("WeChatMainWndForPC", "微信", Rect::new(0, 0, 100, 100)));
+        let other = desktop.create(FakeWindow::new("Notepad", "", Rect::new(0, 0, 100, 100)));
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (_all, _main) = (seen.clone(), seen.clone());
+
+        let mut listener = WinEventListener::with_system(Window::default(), desktop.clone());
+        listener.filter(EventFilter::title_regex("^微信$").unwrap());
+        listener.on(WinEventType::Show, move |evt: &WinEvent| _all.lock().unwrap().push(("all", evt.window)));
+        listener.on_filtered(WinEventType::Show, EventFilter::Class("WeChatMainWndForPC".to_string()), move |evt: &WinEvent| {
+            _main.lock().unwrap().push(("main", evt.window))
+        });
+        listener.install().unwrap();
+
+        for w in [login, main, other].iter() {
+            desktop.set_visible(w.hwnd, false);
+            desktop.set_visible(w.hwnd, true);
+        }
+
+        // the events of other never got queued
+        assert_eq!(listener.pump(), 2);
+        assert_eq!(*seen.lock().unwrap(), vec![("all", login), ("all", main), ("main", main)]);
+    }
 
     #[test]
     fn test_init_hook() {
@@ -353,45 +1042,31 @@ mod tests {
 
         // let _ = Window::default() // for all windows
         let mut listener = Window::from_name(None, "MINGW64:/c/Users/Zoe").unwrap().listen();
-        let _ = listener
-            .on(WinEventType::MoveResizeStart, |evt: &WinEvent| {
-                println!("===> object move start {}!", evt.window);
-            })
-            .on(WinEventType::LocationChange, |evt: &WinEvent| {
-                println!("===> object location change {}!", evt.window);
-            })
-            .on(WinEventType::MoveResizeEnd, move |evt: &WinEvent| {
-                // get the position and set to the child one
-                if let Ok(rect) = evt.window.rect() {
-                    child.set_pos(rect.right_top())
-                }
-            }).start(false);
+        listener.on(WinEventType::MoveResizeStart, |evt: &WinEvent| {
+            println!("===> object move start {}!", evt.window);
+        });
+        listener.on(WinEventType::LocationChange, |evt: &WinEvent| {
+            println!("===> object location change {}!", evt.window);
+        });
+        listener.on(WinEventType::MoveResizeEnd, move |evt: &WinEvent| {
+            // get the position and set to the child one
+            if let Ok(rect) = evt.window.rect() {
+                child.set_pos(rect.right_top())
+            }
+        });
+        let _ = listener.start(false);
 
         let mut lis = Window::default().listen();
-        // if evt.window.class() == "WeChatMainWndForPC" {
-        let _ = lis
-            .on(WinEventType::Create, move |evt: &WinEvent| {
-                if !evt.window.is_valide() || evt.raw_id_object != 0 {
-                    return
-                }
-                let title = evt.window.title();
-                let class = evt.window.class();
-
-                // check is weixin main window
-                if title.is_some() && title.unwrap() == "微信" && class.is_ok() {
-                    match class.unwrap().as_str() {
-                        "WeChatLoginWndForPC" => {
-                            println!("启动了新微信");
-                        },
-                        "WeChatMainWndForPC" => {
-                            println!("微信登录成功");
-                        },
-                        _ => {}
-                    }
-                }
-                
-            })
-            .start(false);
+        // check is weixin main window
+        let wechat = EventFilter::WindowObject & EventFilter::title_regex("^微信$").unwrap();
+        lis.filter(wechat);
+        lis.on_filtered(WinEventType::Create, EventFilter::Class("WeChatLoginWndForPC".to_string()), |_: &WinEvent| {
+            println!("启动了新微信");
+        });
+        lis.on_filtered(WinEventType::Create, EventFilter::Class("WeChatMainWndForPC".to_string()), |_: &WinEvent| {
+            println!("微信登录成功");
+        });
+        let _ = lis.start(false);
 
         // 是否能够将代码放到静态的hook函数中去
 
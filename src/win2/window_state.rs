@@ -0,0 +1,71 @@
+// the show state of a top level window, one value for what is
+// spread over IsWindow, IsWindowVisible, IsIconic, IsZoomed and
+// the DWM cloak attribute.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WindowState {
+    // shown, neither minimized nor maximized
+    #[default]
+    Normal,
+    Maximized,
+    Minimized,
+    // not visible, e.g. `ShowWindow(SW_HIDE)`
+    Hidden,
+    // on another virtual desktop or suspended app
+    Cloaked,
+    // destroyed
+    Gone,
+}
+
+impl WindowState {
+
+    // from the flags, the first one that holds wins
+    pub fn from_flags(exists: bool, visible: bool, minimized: bool, cloaked: bool, maximized: bool) -> Self {
+        if !exists {
+            Self::Gone
+        } else if minimized {
+            Self::Minimized
+        } else if !visible {
+            Self::Hidden
+        } else if cloaked {
+            Self::Cloaked
+        } else if maximized {
+            Self::Maximized
+        } else {
+            Self::Normal
+        }
+    }
+
+    // the user can see the window on the current desktop
+    pub fn is_shown(self) -> bool {
+        matches!(self, Self::Normal | Self::Maximized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WindowState;
+
+    #[test]
+    fn test_from_flags() {
+        use WindowState::*;
+
+        // exists, visible, minimized, cloaked, maximized
+        let cases = [
+            ((true, true, false, false, false), Normal),
+            ((true, true, false, false, true), Maximized),
+            // a minimized window is still visible to win32
+            ((true, true, true, false, false), Minimized),
+            ((true, true, true, false, true), Minimized),
+            ((true, false, false, false, false), Hidden),
+            ((true, true, false, true, true), Cloaked),
+            ((false, true, false, false, false), Gone),
+        ];
+
+        for ((exists, visible, minimized, cloaked, maximized), expected) in cases.iter() {
+            let state = WindowState::from_flags(*exists, *visible, *minimized, *cloaked, *maximized);
+            assert_eq!(state, *expected);
+            assert_eq!(state.is_shown(), matches!(expected, Normal | Maximized));
+        }
+    }
+}
@@ -1,5 +1,11 @@
-use crate::win2::{window::Window, rect::Rect, window_event::{WinEventType, WinEvent}, error::Result};
+use crate::win2::{backend::{Win32System, WindowSystem}, coalesce::{Clock, Coalescer, SystemClock}, dpi::{ScaleFactor, Unit}, monitor::VirtualScreen, window::Window, window_state::WindowState, point::{Offset, Point, Size}, rect::{Insets, Rect}, window_event::{WinEventType, WinEvent, WinEventListener}, error::Result};
 use std::cmp;
+use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bindings::Windows::Win32::Foundation::HWND;
 
 /*
  *                                            
@@ -33,73 +39,219 @@ pub enum AttachDirection {
     RightTop, TopRight,
     RightBottom, BottomRight,
     LeftBottom, BottomLeft,
+    // middle of the edge
+    LeftCenter, TopCenter,
+    RightCenter, BottomCenter,
+    // any point of target to any point of self
+    Anchor(Anchor),
+}
+
+// a point of the target glued to a point of the attached window,
+// both as fractions of width and height from the left top corner.
+//
+// e.g. target (1.0, 0.3) to self (0.0, 0.5) puts the middle of the
+// left edge of self at 30% down the right edge of target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor {
+    pub target: (f64, f64),
+    pub companion: (f64, f64),
+}
+
+impl Anchor {
+    pub fn new(target: (f64, f64), companion: (f64, f64)) -> Self {
+        Anchor { target, companion }
+    }
+
+    // beside the target, i.e. the left edge of one is glued to the
+    // right edge of the other
+    pub fn is_horizontal(self) -> bool {
+        let (tx, cx) = (self.target.0, self.companion.0);
+        (tx == 0.0 || tx == 1.0) && cx == 1.0 - tx
+    }
+
+    // same anchor on the other side of target
+    pub fn mirror(self) -> Self {
+        let (t, c) = (self.target, self.companion);
+        if self.is_horizontal() {
+            Anchor::new((1.0 - t.0, t.1), (1.0 - c.0, c.1))
+        } else {
+            Anchor::new((t.0, 1.0 - t.1), (c.0, 1.0 - c.1))
+        }
+    }
+
+    // same point of target on self, to stay inside of target
+    pub fn inside(self) -> Self {
+        Anchor::new(self.target, self.target)
+    }
+
+    // left top of a window with `size` glued to the target
+    pub fn apply(self, size: Size, target: Rect, fixed: Offset) -> Point {
+        let frac = |v: i32, f: f64| (v as f64 * f).round() as i32;
+
+        let on_target = Offset::new(frac(target.width, self.target.0), frac(target.height, self.target.1));
+        let on_self = Offset::new(frac(size.width, self.companion.0), frac(size.height, self.companion.1));
+
+        target.left_top() + on_target - on_self + fixed
+    }
+}
+
+// how the attached window was placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementKind {
+    // the configured direction fits
+    Preferred,
+    // mirrored to the other side of target
+    Flipped,
+    // moved along the target edge to fit
+    Shifted,
+    // nothing fits, pushed into the work area over the target
+    Overlapped,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    // direction actually used
+    pub dir: AttachDirection,
+    pub kind: PlacementKind,
+    pub rect: Rect,
+}
+
+pub type PlacementCallback = Arc<dyn Fn(&Placement) + Send + Sync>;
+
 impl AttachDirection {
 
-    // we know which direction, so only one max and min
-    // just for one direction.
-    pub fn match_size(self, current: (i32, i32), target: (i32, i32), min: i32, max: i32) -> (i32, i32) {
-        let min_max =|v: i32, min: i32, max: i32| -> i32 {
-            let mut m = v;
-            if min > 0 {
-                m = cmp::max(min, m);
-            }
-            if max > 0 {
-                m = cmp::min(max, m);
-            }
-            m
-        };
+    // the named ones in order, `Anchor` not included
+    pub const NAMED: [AttachDirection; 12] = [
+        Self::LeftTop, Self::TopLeft,
+        Self::RightTop, Self::TopRight,
+        Self::RightBottom, Self::BottomRight,
+        Self::LeftBottom, Self::BottomLeft,
+        Self::LeftCenter, Self::TopCenter,
+        Self::RightCenter, Self::BottomCenter,
+    ];
 
+    // the anchor points of the direction
+    pub fn anchor(self) -> Anchor {
+        let a = Anchor::new;
         match self {
-            Self::TopLeft | Self::TopRight | Self::BottomRight | Self::BottomLeft => {
-                (min_max(target.0, min, max), current.1)
-            },
-            Self::LeftTop | Self::LeftBottom | Self::RightTop | Self::RightBottom => {
-                (current.0, min_max(target.1, min, max))
-            },
+            Self::LeftTop => a((0.0, 0.0), (1.0, 0.0)),
+            Self::TopLeft => a((0.0, 0.0), (0.0, 1.0)),
+            Self::RightTop => a((1.0, 0.0), (0.0, 0.0)),
+            Self::TopRight => a((1.0, 0.0), (1.0, 1.0)),
+            Self::RightBottom => a((1.0, 1.0), (0.0, 1.0)),
+            Self::BottomRight => a((1.0, 1.0), (1.0, 0.0)),
+            Self::LeftBottom => a((0.0, 1.0), (1.0, 1.0)),
+            Self::BottomLeft => a((0.0, 1.0), (0.0, 0.0)),
+            Self::LeftCenter => a((0.0, 0.5), (1.0, 0.5)),
+            Self::TopCenter => a((0.5, 0.0), (0.5, 1.0)),
+            Self::RightCenter => a((1.0, 0.5), (0.0, 0.5)),
+            Self::BottomCenter => a((0.5, 1.0), (0.5, 0.0)),
+            Self::Anchor(anchor) => anchor,
         }
     }
 
-    pub fn apply(self, current: Rect, target: Rect, fixed: (i32, i32)) -> (i32, i32) {
+    // anchor for inside of target, the named ones use the same
+    // point on both, custom anchors are used as they are
+    pub fn inside_anchor(self) -> Anchor {
         match self {
-            Self::LeftTop => {
-                let p = target.left_top();
-                (p.0 + fixed.0 - current.width, p.1 + fixed.1)
-            },
-            Self::TopLeft => {
-                let p = target.left_top();
-                (p.0 + fixed.0, p.1 + fixed.1 - current.height)
-            },
-            Self::RightTop => {
-                let p = target.right_top();
-                (p.0 + fixed.0, p.1 + fixed.1)
-            },
-            Self::TopRight => {
-                let p = target.right_top();
-                (p.0 + fixed.0 - current.width, p.1 + fixed.1 - current.height)
-            },
-            Self::RightBottom => {
-                let p = target.right_bottom();
-                (p.0 + fixed.0, p.1 + fixed.1 - current.height)
+            Self::Anchor(anchor) => anchor,
+            _ => self.anchor().inside(),
+        }
+    }
 
-            },
-            Self::BottomRight => {
-                let p = target.right_bottom();
-                (p.0 + fixed.0 - current.width, p.1 + fixed.1)
+    // the named direction of the anchor if there is one
+    pub fn from_anchor(anchor: Anchor) -> Self {
+        Self::NAMED.iter()
+            .find(|d| d.anchor() == anchor)
+            .copied()
+            .unwrap_or(Self::Anchor(anchor))
+    }
 
-            },
-            Self::LeftBottom => {
-                let p = target.left_bottom();
-                (p.0 + fixed.0 - current.width, p.1 + fixed.1 - current.height)
+    // docked on the left or right side of target
+    pub fn is_horizontal(self) -> bool {
+        self.anchor().is_horizontal()
+    }
 
-            },
-            Self::BottomLeft => {
-                let p = target.left_bottom();
-                (p.0 + fixed.0, p.1 + fixed.1)
+    // same corner on the other side of target
+    pub fn mirror(self) -> Self {
+        Self::from_anchor(self.anchor().mirror())
+    }
 
-            },
+    // fixed offset for the mirrored direction, a gap to the
+    // target stays a gap on the other side.
+    pub fn mirror_offset(self, fixed: Offset) -> Offset {
+        if self.is_horizontal() {
+            Offset::new(-fixed.dx, fixed.dy)
+        } else {
+            Offset::new(fixed.dx, -fixed.dy)
+        }
+    }
+
+    // place like popovers do: the preferred direction, then the mirrored
+    // one, then shift along the target edge, at last overlap the target.
+    pub fn place(self, current: Rect, target: Rect, fixed: Offset, work_area: &Rect) -> Placement {
+        let at = |dir: AttachDirection, fixed: Offset| {
+            Rect::from_point_size(dir.apply(current, target, fixed), current.size())
+        };
+
+        let preferred = at(self, fixed);
+        if work_area.contains_rect(preferred) {
+            return Placement { dir: self, kind: PlacementKind::Preferred, rect: preferred };
+        }
+
+        let mirrored = at(self.mirror(), self.mirror_offset(fixed));
+        if work_area.contains_rect(mirrored) {
+            return Placement { dir: self.mirror(), kind: PlacementKind::Flipped, rect: mirrored };
+        }
+
+        for (dir, rect) in [(self, preferred), (self.mirror(), mirrored)].iter() {
+            // only move along the edge, the other axis is kept
+            let lane = if dir.is_horizontal() {
+                Rect::new(rect.x, work_area.y, rect.width, work_area.height)
+            } else {
+                Rect::new(work_area.x, rect.y, work_area.width, rect.height)
+            };
+
+            let shifted = rect.clamp_into(&lane);
+            if work_area.contains_rect(shifted) {
+                return Placement { dir: *dir, kind: PlacementKind::Shifted, rect: shifted };
+            }
+        }
+
+        Placement {
+            dir: self,
+            kind: PlacementKind::Overlapped,
+            rect: preferred.clamp_into(work_area),
+        }
+    }
+
+    // move of the next window stacked on the same edge,
+    // away from the corner the direction starts at.
+    pub fn stack_offset(self, size: Size, spacing: i32) -> Offset {
+        let c = self.anchor().companion;
+        if self.is_horizontal() {
+            let h = size.height + spacing;
+            Offset::new(0, if c.1 > 0.5 { -h } else { h })
+        } else {
+            let w = size.width + spacing;
+            Offset::new(if c.0 > 0.5 { -w } else { w }, 0)
+        }
+    }
+
+    // match the length of the edge docked to, unless the
+    // constraints already match that axis.
+    pub fn match_edge(self, size: SizeConstraints) -> SizeConstraints {
+        let mut size = size;
+        if self.is_horizontal() {
+            size.match_height = size.match_height.or(Some(1.0));
+        } else {
+            size.match_width = size.match_width.or(Some(1.0));
         }
+        size
+    }
+
+    pub fn apply(self, current: Rect, target: Rect, fixed: Offset) -> Point {
+        self.anchor().apply(current.size(), target, fixed)
     }
 }
 
@@ -111,50 +263,267 @@ impl Window {
     }
 }
 
+// what the companions do when the target state changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompanionAction {
+    Nothing,
+    // target minimized, hidden, cloaked or gone
+    Hide,
+    // target back on screen, show and place at once
+    Show,
+    // target maximized or restored, place again
+    Reflow,
+}
+
+impl CompanionAction {
+    pub fn derive(old: WindowState, new: WindowState) -> Self {
+        match (old.is_shown(), new.is_shown()) {
+            (true, false) => Self::Hide,
+            (false, true) => Self::Show,
+            (true, true) if old != new => Self::Reflow,
+            _ => Self::Nothing,
+        }
+    }
+}
+
+// where the window is put relative to target
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AttachMode {
+    // docked outside of the window rect
+    #[default]
+    Outside,
+    // pinned inside of the client area minus the margins,
+    // e.g. `RightTop` is the right top corner inside
+    Inside(Insets),
+    // cover the client area minus the insets
+    Fill(Insets),
+}
+
+// size of the attached window from the size of target, `None` is
+// no constraint.
+//
+// Matched axes are first set to their fraction of target, then the
+// aspect ratio derives one axis from the other, at last min and max
+// are applied, they win over the aspect ratio when both can't hold.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SizeConstraints {
+    // fraction of the target width or height, 1.0 is the same
+    pub match_width: Option<f64>,
+    pub match_height: Option<f64>,
+    pub min_width: Option<i32>,
+    pub max_width: Option<i32>,
+    pub min_height: Option<i32>,
+    pub max_height: Option<i32>,
+    // width / height, the height follows the width unless
+    // only the height is matched
+    pub aspect_ratio: Option<f64>,
+}
+
+impl SizeConstraints {
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // min and max values from unit to physical pixels
+    pub fn to_physical(self, unit: Unit, scale: ScaleFactor) -> Self {
+        let px = |v: Option<i32>| v.map(|v| unit.to_physical(v, scale));
+        Self {
+            min_width: px(self.min_width),
+            max_width: px(self.max_width),
+            min_height: px(self.min_height),
+            max_height: px(self.max_height),
+            ..self
+        }
+    }
+
+    pub fn apply(&self, current: Size, target: Size) -> Size {
+        let frac = |v: i32, f: f64| (v as f64 * f).round() as i32;
+        let limit = |v: i32, min: Option<i32>, max: Option<i32>| {
+            let v = min.map_or(v, |min| cmp::max(min, v));
+            max.map_or(v, |max| cmp::min(max, v))
+        };
+        let width = |v: i32| limit(v, self.min_width, self.max_width);
+        let height = |v: i32| limit(v, self.min_height, self.max_height);
+
+        let w = self.match_width.map_or(current.width, |f| frac(target.width, f));
+        let h = self.match_height.map_or(current.height, |f| frac(target.height, f));
+
+        match self.aspect_ratio {
+            Some(ratio) if ratio > 0.0 => {
+                if self.match_height.is_some() && self.match_width.is_none() {
+                    let w = width(frac(height(h), ratio));
+                    Size::new(w, height(frac(w, 1.0 / ratio)))
+                } else {
+                    let h = height(frac(width(w), 1.0 / ratio));
+                    Size::new(width(frac(h, ratio)), h)
+                }
+            },
+            _ => Size::new(width(w), height(h)),
+        }
+    }
+}
+
+// options of the attach, can be changed live by the session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttachOptions {
+    // direction
+    pub dir: AttachDirection,
+    // match the length of the docked edge
+    pub match_size: bool,
+    // size from target, with limits
+    pub size: SizeConstraints,
+    // fix the position from target
+    pub fix_pos: Offset,
+    // unit of fix_pos, size limits and insets
+    pub unit: Unit,
+    // keep the window inside the work area of monitor
+    pub keep_in_work_area: bool,
+    // try other places when off screen
+    pub auto_flip: bool,
+    // outside, inside or over the target
+    pub mode: AttachMode,
+    // let mouse input go through to the target
+    pub click_through: bool,
+}
+
+impl Default for AttachOptions {
+    fn default() -> Self {
+        Self {
+            dir: AttachDirection::RightTop,
+            match_size: false,
+            size: SizeConstraints::default(),
+            fix_pos: Offset::zero(),
+            unit: Unit::Physical,
+            keep_in_work_area: false,
+            auto_flip: false,
+            mode: AttachMode::Outside,
+            click_through: false,
+        }
+    }
+}
+
+impl AttachOptions {
+
+    // offset, size limits and insets from unit to physical pixels
+    pub fn to_physical(self, scale: ScaleFactor) -> Self {
+        let px = |v: i32| self.unit.to_physical(v, scale);
+        Self {
+            fix_pos: Offset::new(px(self.fix_pos.dx), px(self.fix_pos.dy)),
+            size: self.size.to_physical(self.unit, scale),
+            mode: match self.mode {
+                AttachMode::Outside => AttachMode::Outside,
+                AttachMode::Inside(insets) => AttachMode::Inside(insets.map(px)),
+                AttachMode::Fill(insets) => AttachMode::Fill(insets.map(px)),
+            },
+            unit: Unit::Physical,
+            ..self
+        }
+    }
+}
+
+// where the attached window goes, no window is touched.
+//
+// `target` is the window rect, or the client area for the inside
+// and fill modes. Values of `opts` are physical pixels, see
+// `AttachOptions::to_physical`. `work_area` is the one of the
+// monitor of target, used by `auto_flip` and `keep_in_work_area`.
+pub fn compute_attached_rect(current: Rect, target: Rect, opts: &AttachOptions, work_area: &Rect) -> Rect {
+    compute_attached_placement(current, target, opts, work_area).rect
+}
+
+// same as `compute_attached_rect`, with the direction actually used
+pub fn compute_attached_placement(current: Rect, target: Rect, opts: &AttachOptions, work_area: &Rect) -> Placement {
+    let area = match opts.mode {
+        AttachMode::Outside => target,
+        AttachMode::Inside(insets) | AttachMode::Fill(insets) => target.inset(insets),
+    };
+
+    // resize first, the position needs the size
+    let mut current = current;
+    let size = if opts.match_size { opts.dir.match_edge(opts.size) } else { opts.size };
+    if !size.is_none() {
+        current.set_size(size.apply(current.size(), area.size()));
+    }
+
+    let preferred = |rect: Rect| Placement { dir: opts.dir, kind: PlacementKind::Preferred, rect };
+    let mut placement = match opts.mode {
+        AttachMode::Outside if opts.auto_flip => opts.dir.place(current, area, opts.fix_pos, work_area),
+        AttachMode::Outside => preferred(
+            Rect::from_point_size(opts.dir.apply(current, area, opts.fix_pos), current.size()),
+        ),
+        // never flipped, the target is all around
+        AttachMode::Inside(_) => preferred(
+            Rect::from_point_size(opts.dir.inside_anchor().apply(current.size(), area, opts.fix_pos), current.size()),
+        ),
+        AttachMode::Fill(_) => preferred(area.translate(opts.fix_pos)),
+    };
+
+    if opts.keep_in_work_area {
+        placement.rect = placement.rect.clamp_into(work_area);
+    }
+    placement
+}
+
 pub struct WindowAttach {
     // self window
     w: Window,
     // target window
     target: Window,
 
-    // direction
-    dir: AttachDirection,
-    // match the size or not with max and min limit
-    match_size: bool,
+    // platform backend
+    sys: Arc<dyn WindowSystem>,
+
+    opts: AttachOptions,
+    on_placement: Option<PlacementCallback>,
+    // limits of the matched edge, set on `opts.size` by `resolved`
     match_size_max: u32,
     match_size_min: u32,
-    // fix the position from target
-    fix_pos: (i32, i32),
+
+    // at most one reposition per interval while target moves
+    interval: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl WindowAttach {
 
     // constructor for window attach
     pub fn new(w: Window, target: Window) -> Self {
+        Self::with_system(w, target, Win32System::shared())
+    }
+
+    // constructor on other backend, e.g. the fake desktop
+    pub fn with_system(w: Window, target: Window, sys: Arc<dyn WindowSystem>) -> Self {
         Self {
-            w, target,
-            dir: AttachDirection::RightTop,
-            match_size: false,
+            w, target, sys,
+            opts: AttachOptions::default(),
+            on_placement: None,
             match_size_max: 0,
             match_size_min: 0,
-            fix_pos: (0, 0),
+            interval: Duration::from_millis(16),
+            clock: Arc::new(SystemClock::default()),
         }
     }
 
+    pub fn options(&mut self, opts: AttachOptions) -> &mut Self {
+        self.opts = opts;
+        self
+    }
+
     pub fn dir(&mut self, direction: AttachDirection) -> &mut Self {
-        self.dir = direction;
+        self.opts.dir = direction;
         self
     }
 
     pub fn match_size(&mut self, enable: bool) -> &mut Self {
-        self.match_size = enable;
+        self.opts.match_size = enable;
         self
     }
 
+    // limits of the matched edge length, on the axis of the direction
+    // when bound. zero is no limit.
     pub fn match_size_limit(&mut self, max: u32, min: u32) -> &mut Self {
-        self.match_size_max = max;
-        self.match_size_min = min;
-        self
+        self.match_size_max(max).match_size_min(min)
     }
 
     pub fn match_size_max(&mut self, max: u32) -> &mut Self {
@@ -167,88 +536,124 @@ impl WindowAttach {
         self
     }
 
-    pub fn fix_pos(&mut self, fixed: (i32, i32)) -> &mut Self {
-        // x, y
-        self.fix_pos = fixed;
+    // size of the window from target, see `SizeConstraints`
+    pub fn size_constraints(&mut self, size: SizeConstraints) -> &mut Self {
+        self.opts.size = size;
         self
     }
 
-    // start the attach
-    pub fn start(&mut self) -> Result<()> {
-        // set the target to be owner
-        self.w.set_owner(self.target);
+    // fraction of the target width and height to take, `None` keeps
+    // the own size of that axis
+    pub fn match_percent(&mut self, width: Option<f64>, height: Option<f64>) -> &mut Self {
+        self.opts.size.match_width = width;
+        self.opts.size.match_height = height;
+        self
+    }
 
+    // width / height to keep
+    pub fn aspect_ratio(&mut self, ratio: f64) -> &mut Self {
+        self.opts.size.aspect_ratio = Some(ratio);
+        self
+    }
 
-        let _dir = self.dir;
-        let _match_size = self.match_size;
-        let _max = self.match_size_max;
-        let _min = self.match_size_min;
-        let _fix_pos = self.fix_pos;
-        let _target = self.target;
-        let _window = self.w;
+    pub fn fix_pos(&mut self, fixed: Offset) -> &mut Self {
+        self.opts.fix_pos = fixed;
+        self
+    }
 
+    // unit of fix_pos and match size limits, with logical the
+    // values are scaled by dpi of the target window.
+    pub fn unit(&mut self, unit: Unit) -> &mut Self {
+        self.opts.unit = unit;
+        self
+    }
 
-        let update_rect = move || {
-            // get the rect of target
-            let target_rect = _target.rect().unwrap();
+    // don't place the window off screen or under the taskbar
+    pub fn keep_in_work_area(&mut self, enable: bool) -> &mut Self {
+        self.opts.keep_in_work_area = enable;
+        self
+    }
 
-            let mut current_rect =  _window.rect().unwrap();
-            let old = current_rect;
-            // resize self, this must be first!
-            // postion needs size
-            if _match_size {
-                let size = _dir.match_size(
-                    (current_rect.width, current_rect.height), 
-                    (target_rect.width, target_rect.height),
-                    _min as _,
-                    _max as _,
-                );
-                current_rect.width = size.0;
-                current_rect.height = size.1;
-            }
-            // this shoudl be in a function
-            let p = _dir.apply(current_rect, target_rect, _fix_pos);
-            current_rect.x = p.0;
-            current_rect.y = p.1;
-            if !old.eq(&current_rect) {
-                // update 
-                println!("change rect {}", current_rect);
-                _window.set_rect(&current_rect, false);
-            }
-            println!("same one");
-        };
+    // when the window would go off the work area of target's monitor,
+    // mirror the direction, shift along the edge or overlap the target.
+    pub fn auto_flip(&mut self, enable: bool) -> &mut Self {
+        self.opts.auto_flip = enable;
+        self
+    }
 
-        // init udpate
-        _window.show();
-        update_rect();
+    // dock outside, pin inside or cover the client area of target,
+    // the insets are in `unit` too.
+    pub fn mode(&mut self, mode: AttachMode) -> &mut Self {
+        self.opts.mode = mode;
+        self
+    }
 
-        // start the event hook
-        let mut listener = self.target.listen();
-        let _ = listener
-            .on(WinEventType::LocationChange, move |evt: &WinEvent| {
-                // TODO: too many events
-                println!("evt.obejct {}, evt.child {}", evt.raw_id_object, evt.raw_id_child);
-                if 0 == evt.raw_id_object { update_rect(); }
-            })
-            .on(WinEventType::MoveResizeEnd, move |evt: &WinEvent| {
-                // reset size and pos
-                // get the old place???
-                update_rect();
-            })
-            .on(WinEventType::Show, move |evt: &WinEvent| {
-                if 0 == evt.raw_id_object {
-                    println!("window show");
-                    _window.show();
-                }
-            })
-            .on(WinEventType::Hide, move |evt: &WinEvent| {
-                if 0 == evt.raw_id_object {
-                    _window.hidden();
-                }
-            })
-            .start(true);
+    // for overlays, clicks go to the target below
+    pub fn click_through(&mut self, enable: bool) -> &mut Self {
+        self.opts.click_through = enable;
+        self
+    }
+
+    // called when the direction or kind of placement changes
+    pub fn on_placement<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(&Placement) + Send + Sync + 'static
+    {
+        self.on_placement = Some(Arc::new(cb));
+        self
+    }
+
+    // min time between repositions while the target is moving, the
+    // final position is always applied. zero moves on every event.
+    pub fn coalesce(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    // clock for coalescing, e.g. a manual one in tests
+    pub fn clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+
+    // start the attach, events are processed in a background thread
+    // until the returned session is dropped.
+    pub fn start(&mut self) -> Result<AttachSession> {
+        self.group().start()
+    }
+
+    // place the window and install the event hook on target,
+    // events are processed only by `pump` of the session.
+    pub fn bind(&mut self) -> Result<AttachSession> {
+        self.group().bind()
+    }
 
-        Ok(())
+    // a window attach is a group with one companion
+    fn group(&self) -> AttachGroup {
+        let mut group = AttachGroup::with_system(self.target, self.sys.clone());
+        group.coalesce(self.interval).clock(self.clock.clone());
+        group.companions.push((self.w, self.resolved(), self.on_placement.clone()));
+        group
+    }
+
+    // the options with the limits of `match_size_limit` on the axis
+    // matched by the final direction, they limit only a matched edge
+    fn resolved(&self) -> AttachOptions {
+        let mut opts = self.opts;
+        if !opts.match_size {
+            return opts;
+        }
+
+        let limit = |v: u32| if v > 0 { Some(v as i32) } else { None };
+        let size = &mut opts.size;
+        let (min, max) = if opts.dir.is_horizontal() {
+            (&mut size.min_height, &mut size.max_height)
+        } else {
+            (&mut size.min_width, &mut size.max_width)
+        };
+        *min = limit(self.match_size_min).or(*min);
+        *max = limit(self.match_size_max).or(*max);
+        opts
     }
 }
 
@@ -258,25 +663,1371 @@ impl Drop for WindowAttach {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::win2::window::Window;
+// many windows attached to one target, sharing one event hook.
+//
+// Companions with the same direction are stacked along the target
+// edge in the order they were added, `spacing` apart.
+pub struct AttachGroup {
+    target: Window,
+    sys: Arc<dyn WindowSystem>,
 
-    use super::{AttachDirection};
+    companions: Vec<(Window, AttachOptions, Option<PlacementCallback>)>,
+    spacing: i32,
 
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+}
 
-    #[test]
-    fn test_demo() {
-        let child = Window::from_name(None, "MINGW64:/d/Zoe").unwrap();
-        let target = Window::from_name(None, "MINGW64:/c/Users/Zoe").unwrap();
-        let target = Window::from_name(Some("WeChatMainWndForPC"), "微信").unwrap(); 
+impl AttachGroup {
 
-        let _ = child.attach_to(target)
-            .match_size(true)
-            .dir(AttachDirection::RightTop)
-            .match_size_min(200)
-            .match_size_max(800)
-            .fix_pos((-10, 0))
-            .start();
+    pub fn new(target: Window) -> Self {
+        Self::with_system(target, Win32System::shared())
+    }
+
+    pub fn with_system(target: Window, sys: Arc<dyn WindowSystem>) -> Self {
+        Self {
+            target, sys,
+            companions: vec![],
+            spacing: 0,
+            interval: Duration::from_millis(16),
+            clock: Arc::new(SystemClock::default()),
+        }
+    }
+
+    pub fn add(&mut self, w: Window, opts: AttachOptions) -> &mut Self {
+        self.companions.push((w, opts, None));
+        self
+    }
+
+    // called when the placement of the companion added last changes
+    pub fn on_placement<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(&Placement) + Send + Sync + 'static
+    {
+        if let Some(last) = self.companions.last_mut() {
+            last.2 = Some(Arc::new(cb));
+        }
+        self
+    }
+
+    // gap between companions stacked on the same edge,
+    // in the unit of each companion
+    pub fn spacing(&mut self, spacing: i32) -> &mut Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn coalesce(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+
+    // start the attach, events are processed in a background thread
+    // until the returned session is dropped.
+    pub fn start(&mut self) -> Result<AttachSession> {
+        let mut session = self.bind()?;
+        session.listener.start(false)?;
+
+        Ok(session)
+    }
+
+    // place the companions and install one event hook on target,
+    // events are processed only by `pump` of the session.
+    pub fn bind(&mut self) -> Result<AttachSession> {
+        if self.companions.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "attach group without companions"));
+        }
+        let sys = self.sys.clone();
+
+        let mut companions = vec![];
+        for (w, opts, on_placement) in self.companions.iter() {
+            // remember to restore on detach
+            let original_owner = sys.owner(w.hwnd);
+            let original_rect = sys.rect(w.hwnd).ok();
+
+            // set the target to be owner
+            sys.set_owner(w.hwnd, self.target.hwnd)?;
+
+            companions.push(Companion {
+                w: *w,
+                opts: *opts,
+                on_placement: on_placement.clone(),
+                last_placement: None,
+                current: None,
+                click_through: false,
+                original_owner,
+                original_rect,
+            });
+        }
+
+        let state = Arc::new(AttachState {
+            target: self.target,
+            sys: sys.clone(),
+            companions: Mutex::new(companions),
+            spacing: self.spacing,
+            paused: AtomicBool::new(false),
+            target_state: Mutex::new(sys.window_state(self.target.hwnd)),
+            coalescer: Mutex::new(Coalescer::new(self.interval)),
+            clock: self.clock.clone(),
+        });
+
+        // init udpate, hidden while target is minimized or so
+        let shown = state.target_state().is_shown();
+        state.set_visible(shown);
+        state.update();
+
+        // start the event hook
+        let (_move, _end, _show, _hide) = (state.clone(), state.clone(), state.clone(), state.clone());
+        let (_focus, _idle) = (state.clone(), state.clone());
+        let mut listener = WinEventListener::with_system(self.target, sys);
+        listener.on(WinEventType::LocationChange, move |evt: &WinEvent| {
+            if 0 != evt.raw_id_object { return; }
+            // maximize and restore come as location changes,
+            // dragging sends hundreds of them, coalesced
+            if _move.sync_target() == CompanionAction::Nothing {
+                _move.request_update();
+            }
+        });
+        listener.on(WinEventType::MoveResizeEnd, move |_evt: &WinEvent| {
+            // always apply the final position
+            _end.flush_update();
+        });
+        // minimize and cloak come as hide and show
+        listener.on(WinEventType::Show, move |evt: &WinEvent| {
+            if 0 == evt.raw_id_object { _show.sync_target(); }
+        });
+        listener.on(WinEventType::Hide, move |evt: &WinEvent| {
+            if 0 == evt.raw_id_object { _hide.sync_target(); }
+        });
+        listener.on(WinEventType::FocusChange, move |evt: &WinEvent| {
+            if 0 == evt.raw_id_object {
+                _focus.sync_target();
+                _focus.raise();
+            }
+        });
+        listener
+            .on_idle(move || _idle.poll_update())
+            .install()?;
+
+        Ok(AttachSession { state, listener })
+    }
+}
+
+// one attached window of the group
+struct Companion {
+    w: Window,
+    opts: AttachOptions,
+    on_placement: Option<PlacementCallback>,
+    last_placement: Option<(AttachDirection, PlacementKind)>,
+
+    // last computed rect
+    current: Option<Rect>,
+    // click through as applied to the window
+    click_through: bool,
+
+    original_owner: HWND,
+    original_rect: Option<Rect>,
+}
+
+// shared by the session and the event handlers
+struct AttachState {
+    target: Window,
+    sys: Arc<dyn WindowSystem>,
+
+    companions: Mutex<Vec<Companion>>,
+    spacing: i32,
+    paused: AtomicBool,
+    target_state: Mutex<WindowState>,
+
+    coalescer: Mutex<Coalescer>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AttachState {
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    // update now or later if there was one just now
+    fn request_update(&self) {
+        let run = self.coalescer.lock().unwrap().request(self.clock.now());
+        if run {
+            self.update();
+        }
+    }
+
+    // run the kept update when it is due
+    fn poll_update(&self) {
+        let run = self.coalescer.lock().unwrap().poll(self.clock.now());
+        if run {
+            self.update();
+        }
+    }
+
+    // update now and drop the kept one
+    fn flush_update(&self) {
+        self.coalescer.lock().unwrap().flush(self.clock.now());
+        self.update();
+    }
+
+    fn target_state(&self) -> WindowState {
+        *self.target_state.lock().unwrap()
+    }
+
+    // read the state of target again and follow it
+    fn sync_target(&self) -> CompanionAction {
+        let new = self.sys.window_state(self.target.hwnd);
+        let old = std::mem::replace(&mut *self.target_state.lock().unwrap(), new);
+
+        let action = CompanionAction::derive(old, new);
+        match action {
+            CompanionAction::Hide => self.set_visible(false),
+            CompanionAction::Show => {
+                self.set_visible(true);
+                self.flush_update();
+            },
+            CompanionAction::Reflow => self.flush_update(),
+            CompanionAction::Nothing => {},
+        }
+        action
+    }
+
+    // right above the target again, e.g. after it was activated
+    fn raise(&self) {
+        let companions = self.companions.lock().unwrap();
+        if self.is_paused() || !self.target_state().is_shown() {
+            return;
+        }
+        for c in companions.iter() {
+            if let Some(rect) = c.current {
+                let _ = self.sys.set_rect_above(c.w.hwnd, &rect, self.target.hwnd);
+            }
+        }
+    }
+
+    // mirror the visibility of target
+    fn set_visible(&self, visible: bool) {
+        let companions = self.companions.lock().unwrap();
+        if self.is_paused() {
+            return;
+        }
+        for c in companions.iter() {
+            self.sys.set_visible(c.w.hwnd, visible);
+        }
+    }
+
+    fn update(&self) {
+        // held until the windows are moved, pausing waits for it
+        let mut companions = self.companions.lock().unwrap();
+        // a minimized target is far off screen, nothing to follow
+        if self.is_paused() || !self.target_state().is_shown() {
+            return;
+        }
+
+        let sys = &self.sys;
+
+        // get the rect of target
+        let target_rect = match sys.rect(self.target.hwnd) {
+            Ok(rect) => rect,
+            Err(_) => return,
+        };
+        let scale = sys.scale_factor(self.target.hwnd);
+        // use the monitor of target to decide
+        let work_area = VirtualScreen::new(sys.monitors()).nearest(&target_rect).map(|m| m.work_area);
+
+        // how far the next companion on each edge is pushed,
+        // inside and outside ones are stacked apart
+        let mut stacked: Vec<((AttachDirection, bool), Offset)> = vec![];
+        let mut changed = vec![];
+
+        for c in companions.iter_mut() {
+            let opts = c.opts;
+            let mut current_rect = match sys.rect(c.w.hwnd) {
+                Ok(rect) => rect,
+                Err(_) => continue,
+            };
+            let old = current_rect;
+
+            // inside modes go by the client area
+            let area = match opts.mode {
+                AttachMode::Outside => target_rect,
+                AttachMode::Inside(_) | AttachMode::Fill(_) => match sys.client_rect(self.target.hwnd) {
+                    Ok(rect) => rect,
+                    Err(_) => continue,
+                },
+            };
+
+            // configured values to physical pixels, pushed
+            // away from the ones before us on the same edge
+            let key = (opts.dir, opts.mode != AttachMode::Outside);
+            let stack = match opts.mode {
+                AttachMode::Fill(_) => Offset::zero(),
+                _ => stacked.iter().find(|(k, _)| *k == key).map(|(_, o)| *o).unwrap_or_default(),
+            };
+            let mut layout = opts.to_physical(scale);
+            layout.fix_pos = layout.fix_pos + stack;
+
+            // off every monitor, nothing to flip or clamp into
+            if work_area.is_none() {
+                layout.auto_flip = false;
+                layout.keep_in_work_area = false;
+            }
+            let placement = compute_attached_placement(current_rect, area, &layout, &work_area.unwrap_or_default());
+            current_rect = placement.rect;
+
+            if c.click_through != opts.click_through
+                && sys.set_click_through(c.w.hwnd, opts.click_through).is_ok()
+            {
+                c.click_through = opts.click_through;
+            }
+            // skip when nothing changed
+            if !old.eq(&current_rect) {
+                let _ = sys.set_rect_above(c.w.hwnd, &current_rect, self.target.hwnd);
+            }
+            c.current = Some(current_rect);
+
+            // the next one on this edge goes after us
+            let spacing = opts.unit.to_physical(self.spacing, scale);
+            let next = stack + opts.dir.stack_offset(current_rect.size(), spacing);
+            match stacked.iter_mut().find(|(k, _)| *k == key) {
+                _ if matches!(opts.mode, AttachMode::Fill(_)) => {},
+                Some(s) => s.1 = next,
+                None => stacked.push((key, next)),
+            }
+
+            // report the used direction
+            let used = (placement.dir, placement.kind);
+            if c.last_placement.replace(used) != Some(used) {
+                if let Some(cb) = &c.on_placement {
+                    changed.push((cb.clone(), placement));
+                }
+            }
+        }
+        drop(companions);
+
+        for (cb, placement) in changed {
+            cb(&placement);
+        }
+    }
+}
+
+// handle of a running attach, dropping it detaches the windows.
+//
+// Methods without an index act on the first companion, the only one
+// of a `WindowAttach`.
+pub struct AttachSession {
+    state: Arc<AttachState>,
+    listener: WinEventListener,
+}
+
+impl AttachSession {
+
+    // stop following the target, the windows stay where they are
+    pub fn pause(&self) {
+        let _companions = self.state.companions.lock().unwrap();
+        self.state.paused.store(true, Ordering::SeqCst);
+    }
+
+    // follow the target again, moves the windows at once
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::SeqCst);
+        self.state.update();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.is_paused()
+    }
+
+    // number of companions
+    pub fn len(&self) -> usize {
+        self.state.companions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn set_dir(&self, dir: AttachDirection) {
+        self.change(0, |opts| opts.dir = dir);
+    }
+
+    pub fn set_fix_pos(&self, fixed: Offset) {
+        self.change(0, |opts| opts.fix_pos = fixed);
+    }
+
+    // change all options at once
+    pub fn set_options(&self, opts: AttachOptions) {
+        self.set_options_of(0, opts);
+    }
+
+    pub fn options(&self) -> AttachOptions {
+        self.options_of(0).unwrap_or_default()
+    }
+
+    // last rect computed for the window
+    pub fn current_rect(&self) -> Option<Rect> {
+        self.current_rect_of(0)
+    }
+
+    // change the options of one companion, ignored when out of range
+    pub fn set_options_of(&self, index: usize, opts: AttachOptions) {
+        self.change(index, |o| *o = opts);
+    }
+
+    pub fn options_of(&self, index: usize) -> Option<AttachOptions> {
+        self.state.companions.lock().unwrap().get(index).map(|c| c.opts)
+    }
+
+    pub fn current_rect_of(&self, index: usize) -> Option<Rect> {
+        self.state.companions.lock().unwrap().get(index).and_then(|c| c.current)
+    }
+
+    fn change(&self, index: usize, f: impl FnOnce(&mut AttachOptions)) {
+        if let Some(c) = self.state.companions.lock().unwrap().get_mut(index) {
+            f(&mut c.opts);
+        }
+        self.state.update();
+    }
+
+    // process queued events on current thread, for sessions from `bind`
+    pub fn pump(&self) -> usize {
+        self.listener.pump()
+    }
+
+    // unhook and restore the windows, same as dropping the session
+    pub fn detach(self) {}
+}
+
+impl Drop for AttachSession {
+    fn drop(&mut self) {
+        // no more updates from handlers still running
+        self.pause();
+
+        let sys = &self.state.sys;
+        for c in self.state.companions.lock().unwrap().iter() {
+            let _ = sys.set_owner(c.w.hwnd, c.original_owner);
+            if c.click_through {
+                let _ = sys.set_click_through(c.w.hwnd, false);
+            }
+            if let Some(rect) = c.original_rect {
+                let _ = sys.set_rect(c.w.hwnd, &rect, false);
+            }
+        }
+
+        // the listener unhooks when dropped after us
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::win2::{backend::WindowSystem, coalesce::ManualClock, dpi::{ScaleFactor, Unit}, fake::{FakeDesktop, FakeWindow}, message_loop::MessageLoop, point::{Offset, Size}, rect::{Insets, Rect}, window::Window, window_state::WindowState};
+
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use bindings::Windows::Win32::Foundation::HWND;
+
+    use super::{AttachDirection, AttachGroup, AttachMode, AttachOptions, CompanionAction, Placement, PlacementKind, SizeConstraints, WindowAttach};
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn test_attach_follow_target() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(0, 0, 100, 200)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(500, 500, 50, 50)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::RightTop)
+            .match_size(true)
+            .bind()
+            .unwrap();
+
+        // placed and owned at once
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(100, 0, 50, 200));
+        assert_eq!(desktop.get(child.hwnd).unwrap().owner, target.hwnd);
+
+        desktop.drag(target.hwnd, &[rect(10, 10, 100, 200), rect(20, 30, 100, 200)]);
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(120, 30, 50, 200));
+
+        desktop.set_visible(target.hwnd, false);
+        session.pump();
+        assert!(!desktop.is_visible(child.hwnd));
+
+        desktop.set_visible(target.hwnd, true);
+        session.pump();
+        assert!(desktop.is_visible(child.hwnd));
+    }
+
+    #[test]
+    fn test_attach_logical_units() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(0, 0, 600, 300)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 100, 100)));
+        desktop.set_scale(target.hwnd, ScaleFactor(1.5));
+
+        let _session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::BottomLeft)
+            .match_size(true)
+            .match_size_max(200)
+            .fix_pos(Offset::new(10, -4))
+            .unit(Unit::Logical)
+            .bind()
+            .unwrap();
+
+        // 200 logical max is 300 physical, offset is scaled too
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(15, 294, 300, 100));
+    }
+
+    #[test]
+    fn test_attach_keep_in_work_area() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(1800, 900, 100, 100)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+
+        let _session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::RightTop)
+            .keep_in_work_area(true)
+            .bind()
+            .unwrap();
+
+        // pushed left of the screen edge and above the taskbar
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(1720, 740, 200, 300));
+    }
+
+
+    #[test]
+    fn test_apply_anchors() {
+        use AttachDirection::*;
+
+        let target = rect(100, 200, 400, 300);
+        let child = rect(0, 0, 50, 30);
+        let cases = [
+            // the corners
+            (LeftTop, rect(50, 200, 50, 30)),
+            (TopLeft, rect(100, 170, 50, 30)),
+            (RightTop, rect(500, 200, 50, 30)),
+            (TopRight, rect(450, 170, 50, 30)),
+            (RightBottom, rect(500, 470, 50, 30)),
+            (BottomRight, rect(450, 500, 50, 30)),
+            (LeftBottom, rect(50, 470, 50, 30)),
+            (BottomLeft, rect(100, 500, 50, 30)),
+            // the middles
+            (LeftCenter, rect(50, 335, 50, 30)),
+            (TopCenter, rect(275, 170, 50, 30)),
+            (RightCenter, rect(500, 335, 50, 30)),
+            (BottomCenter, rect(275, 500, 50, 30)),
+            // 30% down the right edge, by the middle of self
+            (Anchor(super::Anchor::new((1.0, 0.3), (0.0, 0.5))), rect(500, 275, 50, 30)),
+            // centered over the target
+            (Anchor(super::Anchor::new((0.5, 0.5), (0.5, 0.5))), rect(275, 335, 50, 30)),
+            // a quarter in, hanging below
+            (Anchor(super::Anchor::new((0.25, 1.0), (0.0, 0.0))), rect(200, 500, 50, 30)),
+        ];
+
+        for (dir, expected) in cases.iter() {
+            let at = dir.apply(child, target, Offset::zero());
+            assert_eq!(Rect::from_point_size(at, child.size()), *expected, "{:?}", dir);
+
+            // the fixed offset is added on top
+            assert_eq!(dir.apply(child, target, Offset::new(3, -4)), at + Offset::new(3, -4));
+        }
+    }
+
+    #[test]
+    fn test_anchor_mirror_and_names() {
+        use AttachDirection::*;
+
+        let cases = [
+            (LeftTop, RightTop, true),
+            (TopRight, BottomRight, false),
+            (LeftBottom, RightBottom, true),
+            (LeftCenter, RightCenter, true),
+            (TopCenter, BottomCenter, false),
+            (Anchor(super::Anchor::new((1.0, 0.3), (0.0, 0.5))), Anchor(super::Anchor::new((0.0, 0.3), (1.0, 0.5))), true),
+            (Anchor(super::Anchor::new((0.25, 1.0), (0.0, 0.0))), Anchor(super::Anchor::new((0.25, 0.0), (0.0, 1.0))), false),
+        ];
+
+        for (dir, mirrored, horizontal) in cases.iter() {
+            assert_eq!(dir.mirror(), *mirrored, "{:?}", dir);
+            assert_eq!(mirrored.mirror(), *dir, "{:?}", dir);
+            assert_eq!(dir.is_horizontal(), *horizontal, "{:?}", dir);
+        }
+
+        // anchors of the named ones get their names back
+        for dir in AttachDirection::NAMED.iter() {
+            assert_eq!(AttachDirection::from_anchor(dir.anchor()), *dir);
+        }
+        let custom = super::Anchor::new((1.0, 0.3), (0.0, 0.5));
+        assert_eq!(AttachDirection::from_anchor(custom), AttachDirection::Anchor(custom));
+    }
+
+    #[test]
+    fn test_attach_centered() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 100)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::RightCenter)
+            .auto_flip(true)
+            .bind()
+            .unwrap();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(500, 250, 200, 100));
+
+        // at the right edge of the screen it flips to the left middle
+        desktop.move_to(target.hwnd, rect(1520, 100, 400, 400));
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(1320, 250, 200, 100));
+    }
+
+    #[test]
+    fn test_size_constraints() {
+        let current = Size::new(200, 100);
+        let target = Size::new(800, 600);
+        let none = SizeConstraints::default();
+        let cases = [
+            (none, Size::new(200, 100)),
+            (SizeConstraints { match_height: Some(1.0), ..none }, Size::new(200, 600)),
+            (SizeConstraints { match_width: Some(0.5), match_height: Some(0.25), ..none }, Size::new(400, 150)),
+            // limits without matching clamp the own size
+            (SizeConstraints { min_width: Some(300), max_height: Some(80), ..none }, Size::new(300, 80)),
+            (SizeConstraints { match_height: Some(1.0), max_height: Some(500), min_width: Some(250), ..none }, Size::new(250, 500)),
+            // the height follows the width
+            (SizeConstraints { aspect_ratio: Some(2.0), ..none }, Size::new(200, 100)),
+            (SizeConstraints { match_width: Some(0.5), aspect_ratio: Some(4.0 / 3.0), ..none }, Size::new(400, 300)),
+            // only the height matched, the width follows it
+            (SizeConstraints { match_height: Some(0.5), aspect_ratio: Some(0.5), ..none }, Size::new(150, 300)),
+            // limits win, the other axis is derived again
+            (SizeConstraints { match_width: Some(1.0), aspect_ratio: Some(2.0), max_height: Some(200), ..none }, Size::new(400, 200)),
+            (SizeConstraints { match_width: Some(1.0), aspect_ratio: Some(2.0), max_height: Some(200), max_width: Some(300), ..none }, Size::new(300, 150)),
+            // a bad ratio is ignored
+            (SizeConstraints { aspect_ratio: Some(0.0), ..none }, Size::new(200, 100)),
+        ];
+
+        for (size, expected) in cases.iter() {
+            assert_eq!(size.apply(current, target), *expected, "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn test_match_edge() {
+        let none = SizeConstraints::default();
+        assert_eq!(AttachDirection::RightTop.match_edge(none).match_height, Some(1.0));
+        assert_eq!(AttachDirection::TopCenter.match_edge(none).match_width, Some(1.0));
+        // keeps what was set
+        let half = SizeConstraints { match_height: Some(0.5), ..none };
+        assert_eq!(AttachDirection::LeftBottom.match_edge(half), half);
+
+        let limits = SizeConstraints { min_width: Some(10), max_height: Some(20), match_width: Some(0.5), ..none };
+        let physical = limits.to_physical(Unit::Logical, ScaleFactor(1.5));
+        assert_eq!((physical.min_width, physical.max_height, physical.match_width), (Some(15), Some(30), Some(0.5)));
+        assert_eq!(physical.max_width, None);
+    }
+
+    #[test]
+    fn test_attach_match_percent_aspect() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 800, 600)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 50, 50)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::BottomLeft)
+            .match_percent(Some(0.5), None)
+            .aspect_ratio(16.0 / 9.0)
+            .bind()
+            .unwrap();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(100, 700, 400, 225));
+
+        desktop.move_to(target.hwnd, rect(100, 100, 320, 200));
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(100, 300, 160, 90));
+
+        // the legacy limits land on the axis matched by the final
+        // direction, whatever the order of the calls
+        let mut attach = WindowAttach::with_system(child, target, desktop.clone());
+        attach.match_size(true).dir(AttachDirection::LeftTop).match_size_limit(800, 200);
+        let size = attach.resolved().size;
+        assert_eq!((size.min_height, size.max_height, size.min_width), (Some(200), Some(800), None));
+        attach.match_size_max(0);
+        assert_eq!(attach.resolved().size.max_height, None);
+
+        let mut attach = WindowAttach::with_system(child, target, desktop.clone());
+        attach.match_size(true).match_size_max(300).dir(AttachDirection::BottomLeft);
+        let size = attach.resolved().size;
+        assert_eq!((size.max_width, size.max_height), (Some(300), None));
+        attach.dir(AttachDirection::LeftTop);
+        let size = attach.resolved().size;
+        assert_eq!((size.max_width, size.max_height), (None, Some(300)));
+
+        // only a matched edge is limited
+        attach.match_size(false);
+        assert!(attach.resolved().size.is_none());
+    }
+
+    #[test]
+    fn test_compute_attached_rect() {
+        use AttachDirection::*;
+
+        let target = rect(100, 200, 400, 300);
+        let child = rect(0, 0, 50, 30);
+        let work_area = rect(0, 0, 1920, 1040);
+        let opts = |dir: AttachDirection| AttachOptions { dir, ..Default::default() };
+        let matched = |dir: AttachDirection| AttachOptions { dir, match_size: true, ..Default::default() };
+        let custom = |t: (f64, f64), c: (f64, f64)| Anchor(super::Anchor::new(t, c));
+
+        let cases = [
+            // the docked edge is matched
+            (target, matched(LeftTop), rect(50, 200, 50, 300)),
+            (target, matched(TopLeft), rect(100, 170, 400, 30)),
+            (target, matched(RightTop), rect(500, 200, 50, 300)),
+            (target, matched(TopRight), rect(100, 170, 400, 30)),
+            (target, matched(RightBottom), rect(500, 200, 50, 300)),
+            (target, matched(BottomRight), rect(100, 500, 400, 30)),
+            (target, matched(LeftBottom), rect(50, 200, 50, 300)),
+            (target, matched(BottomLeft), rect(100, 500, 400, 30)),
+            (target, matched(LeftCenter), rect(50, 200, 50, 300)),
+            (target, matched(TopCenter), rect(100, 170, 400, 30)),
+            (target, matched(RightCenter), rect(500, 200, 50, 300)),
+            (target, matched(BottomCenter), rect(100, 500, 400, 30)),
+            (target, matched(custom((1.0, 0.3), (0.0, 0.5))), rect(500, 140, 50, 300)),
+            (target, matched(custom((0.25, 1.0), (0.0, 0.0))), rect(200, 500, 400, 30)),
+            // fixed offset
+            (target, AttachOptions { fix_pos: Offset::new(8, -4), ..opts(RightTop) }, rect(508, 196, 50, 30)),
+            (target, AttachOptions { fix_pos: Offset::new(-8, 0), ..opts(LeftBottom) }, rect(42, 470, 50, 30)),
+            // sized from target with limits
+            (target, AttachOptions {
+                size: SizeConstraints { match_height: Some(0.5), min_width: Some(80), ..Default::default() },
+                ..opts(RightTop)
+            }, rect(500, 200, 80, 150)),
+            (target, AttachOptions {
+                size: SizeConstraints { max_width: Some(300), ..Default::default() },
+                ..matched(BottomCenter)
+            }, rect(150, 500, 300, 30)),
+            // pushed into the work area
+            (rect(1700, 900, 200, 100), AttachOptions { keep_in_work_area: true, ..opts(RightBottom) }, rect(1870, 970, 50, 30)),
+            (rect(1700, 900, 200, 100), opts(RightBottom), rect(1900, 970, 50, 30)),
+            // flipped to the other side, the gap stays a gap
+            (rect(1700, 100, 200, 100), AttachOptions { auto_flip: true, ..opts(RightTop) }, rect(1650, 100, 50, 30)),
+            (rect(1700, 100, 200, 100), AttachOptions { auto_flip: true, fix_pos: Offset::new(8, 0), ..opts(RightTop) }, rect(1642, 100, 50, 30)),
+            (rect(100, 0, 200, 100), AttachOptions { auto_flip: true, ..opts(TopCenter) }, rect(175, 100, 50, 30)),
+            // inside and over the target
+            (target, AttachOptions { mode: AttachMode::Inside(Insets::new(0, 10, 10, 0)), ..opts(RightTop) }, rect(440, 210, 50, 30)),
+            (target, AttachOptions { mode: AttachMode::Inside(Insets::zero()), ..opts(custom((0.5, 0.5), (0.5, 0.5))) }, rect(275, 335, 50, 30)),
+            (target, AttachOptions { mode: AttachMode::Inside(Insets::uniform(10)), ..matched(BottomCenter) }, rect(110, 460, 380, 30)),
+            (target, AttachOptions { mode: AttachMode::Fill(Insets::uniform(5)), ..opts(LeftTop) }, rect(105, 205, 390, 290)),
+            (target, AttachOptions { mode: AttachMode::Fill(Insets::zero()), fix_pos: Offset::new(3, 4), ..opts(LeftTop) }, rect(103, 204, 400, 300)),
+            // inside is never flipped
+            (rect(1800, 100, 100, 100), AttachOptions { mode: AttachMode::Inside(Insets::zero()), auto_flip: true, ..opts(RightTop) }, rect(1850, 100, 50, 30)),
+        ];
+
+        for (target, opts, expected) in cases.iter() {
+            let got = super::compute_attached_rect(child, *target, opts, &work_area);
+            assert_eq!(got, *expected, "{:?} of {:?}", opts, target);
+        }
+
+        // the used direction is reported
+        let placement = super::compute_attached_placement(child, rect(1700, 100, 200, 100), &AttachOptions { auto_flip: true, ..opts(RightTop) }, &work_area);
+        assert_eq!((placement.dir, placement.kind), (LeftTop, PlacementKind::Flipped));
+    }
+
+    #[test]
+    fn test_options_to_physical() {
+        let opts = AttachOptions {
+            fix_pos: Offset::new(10, -4),
+            size: SizeConstraints { match_width: Some(0.5), max_height: Some(200), ..Default::default() },
+            mode: AttachMode::Inside(Insets::new(2, 4, 6, 8)),
+            unit: Unit::Logical,
+            ..Default::default()
+        };
+
+        assert_eq!(opts.to_physical(ScaleFactor(1.5)), AttachOptions {
+            fix_pos: Offset::new(15, -6),
+            size: SizeConstraints { match_width: Some(0.5), max_height: Some(300), ..Default::default() },
+            mode: AttachMode::Inside(Insets::new(3, 6, 9, 12)),
+            unit: Unit::Physical,
+            ..Default::default()
+        });
+
+        // physical values are kept
+        let physical = AttachOptions { unit: Unit::Physical, ..opts };
+        assert_eq!(physical.to_physical(ScaleFactor(1.5)), physical);
+    }
+
+    // xorshift, the same cases on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn range(&mut self, min: i32, max: i32) -> i32 {
+            min + (self.next() % (max - min + 1) as u64) as i32
+        }
+
+        fn fraction(&mut self) -> f64 {
+            self.range(0, 100) as f64 / 100.0
+        }
+
+        fn dir(&mut self) -> AttachDirection {
+            match self.range(0, 12) as usize {
+                12 => AttachDirection::Anchor(super::Anchor::new((self.fraction(), self.fraction()), (self.fraction(), self.fraction()))),
+                i => AttachDirection::NAMED[i],
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_attached_rect_properties() {
+        let work_area = rect(0, 0, 1920, 1040);
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..2000 {
+            let target = rect(rng.range(-500, 2000), rng.range(-500, 1200), rng.range(1, 800), rng.range(1, 600));
+            let child = rect(rng.range(-100, 100), rng.range(-100, 100), rng.range(1, 400), rng.range(1, 400));
+            let dir = rng.dir();
+            let fixed = Offset::new(rng.range(-20, 20), rng.range(-20, 20));
+            let opts = AttachOptions { dir, fix_pos: fixed, ..Default::default() };
+            let compute = |target: Rect, opts: &AttachOptions| super::compute_attached_rect(child, target, opts, &work_area);
+
+            let got = compute(target, &opts);
+            let case = format!("{:?} {:?} {:?}", dir, target, child);
+
+            // only moved, by the anchor and the offset
+            assert_eq!(got.size(), child.size(), "{}", case);
+            assert_eq!(got, Rect::from_point_size(dir.apply(child, target, Offset::zero()), child.size()).translate(fixed), "{}", case);
+
+            // follows the target
+            let moved = Offset::new(rng.range(-300, 300), rng.range(-300, 300));
+            assert_eq!(compute(target.translate(moved), &opts), got.translate(moved), "{}", case);
+
+            // the named ones touch the target without covering it
+            if !matches!(dir, AttachDirection::Anchor(_)) {
+                let docked = compute(target, &AttachOptions { fix_pos: Offset::zero(), ..opts });
+                assert!(!docked.intersects(&target), "{}", case);
+                if dir.is_horizontal() {
+                    assert!(docked.right() == target.left() || docked.left() == target.right(), "{}", case);
+                } else {
+                    assert!(docked.bottom() == target.top() || docked.top() == target.bottom(), "{}", case);
+                }
+            }
+
+            // the docked edge has the length of target's
+            let matched = compute(target, &AttachOptions { match_size: true, ..opts });
+            if dir.is_horizontal() {
+                assert_eq!((matched.width, matched.height), (child.width, target.height), "{}", case);
+            } else {
+                assert_eq!((matched.width, matched.height), (target.width, child.height), "{}", case);
+            }
+
+            // always in the work area when asked for, it's big enough
+            let kept = compute(target, &AttachOptions { keep_in_work_area: true, ..opts });
+            assert!(work_area.contains_rect(kept), "{}", case);
+            if work_area.contains_rect(got) {
+                assert_eq!(kept, got, "{}", case);
+            }
+
+            let flipped = compute(target, &AttachOptions { auto_flip: true, ..opts });
+            assert!(work_area.contains_rect(flipped), "{}", case);
+            if work_area.contains_rect(got) {
+                assert_eq!(flipped, got, "{}", case);
+            }
+
+            // inside stays inside when it fits
+            let inside = compute(target, &AttachOptions { mode: AttachMode::Inside(Insets::zero()), fix_pos: Offset::zero(), ..opts });
+            if child.width <= target.width && child.height <= target.height && !matches!(dir, AttachDirection::Anchor(_)) {
+                assert!(target.contains_rect(inside), "{}", case);
+            }
+
+            // fill ignores the size of self
+            let insets = Insets::uniform(rng.range(0, 10));
+            let fill = compute(target, &AttachOptions { mode: AttachMode::Fill(insets), ..opts });
+            assert_eq!(fill, target.inset(insets).translate(fixed), "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_place_fallback() {
+        use AttachDirection::*;
+        use PlacementKind::*;
+
+        let work = rect(0, 0, 1000, 800);
+        let child = rect(0, 0, 200, 300);
+        let cases = [
+            // room on the right
+            (RightTop, rect(100, 100, 300, 300), RightTop, Preferred, rect(400, 100, 200, 300)),
+            // flush to the right edge, flip to left
+            (RightTop, rect(700, 100, 300, 300), LeftTop, Flipped, rect(500, 100, 200, 300)),
+            (LeftBottom, rect(50, 100, 300, 300), RightBottom, Flipped, rect(350, 100, 200, 300)),
+            (BottomLeft, rect(100, 600, 300, 200), TopLeft, Flipped, rect(100, 300, 200, 300)),
+            // fits on the right only when moved up
+            (RightTop, rect(100, 600, 300, 100), RightTop, Shifted, rect(400, 500, 200, 300)),
+            // right is off screen, left fits when moved up
+            (RightTop, rect(700, 600, 300, 100), LeftTop, Shifted, rect(500, 500, 200, 300)),
+            // the target fills the screen
+            (RightTop, rect(0, 0, 1000, 800), RightTop, Overlapped, rect(800, 0, 200, 300)),
+        ];
+
+        for (dir, target, used, kind, expected) in cases.iter() {
+            let p = dir.place(child, *target, Offset::zero(), &work);
+            assert_eq!(p, Placement { dir: *used, kind: *kind, rect: *expected }, "{:?} {:?}", dir, target);
+            assert!(work.contains_rect(p.rect));
+        }
+    }
+
+    #[test]
+    fn test_place_mirrors_offset() {
+        let work = rect(0, 0, 1000, 800);
+        let p = AttachDirection::RightTop.place(rect(0, 0, 200, 300), rect(700, 100, 300, 300), Offset::new(10, 5), &work);
+        assert_eq!(p.dir, AttachDirection::LeftTop);
+        assert_eq!(p.rect, rect(490, 105, 200, 300));
+
+        for dir in [AttachDirection::LeftTop, AttachDirection::TopRight, AttachDirection::BottomLeft].iter() {
+            assert_eq!(dir.mirror().mirror(), *dir);
+        }
+    }
+
+    #[test]
+    fn test_attach_auto_flip() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+
+        let used = Arc::new(Mutex::new(vec![]));
+        let _used = used.clone();
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::RightTop)
+            .auto_flip(true)
+            .on_placement(move |p: &Placement| _used.lock().unwrap().push((p.dir, p.kind)))
+            .bind()
+            .unwrap();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(500, 100, 200, 300));
+
+        // move the target to the right edge of the screen
+        desktop.drag(target.hwnd, &[rect(1000, 100, 400, 400), rect(1520, 100, 400, 400)]);
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(1320, 100, 200, 300));
+
+        assert_eq!(*used.lock().unwrap(), vec![
+            (AttachDirection::RightTop, PlacementKind::Preferred),
+            (AttachDirection::LeftTop, PlacementKind::Flipped),
+        ]);
+    }
+
+    #[test]
+    fn test_session_live_changes() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::RightTop)
+            .bind()
+            .unwrap();
+        assert_eq!(session.current_rect(), Some(rect(500, 100, 200, 300)));
+
+        session.set_dir(AttachDirection::LeftTop);
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-100, 100, 200, 300));
+
+        session.set_fix_pos(Offset::new(-5, 10));
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-105, 110, 200, 300));
+        assert_eq!(session.options().dir, AttachDirection::LeftTop);
+
+        // paused sessions don't follow
+        session.pause();
+        desktop.move_to(target.hwnd, rect(200, 100, 400, 400));
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-105, 110, 200, 300));
+
+        session.resume();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-5, 110, 200, 300));
+        assert_eq!(session.current_rect(), Some(rect(-5, 110, 200, 300)));
+    }
+
+    #[test]
+    fn test_session_detach_restores() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let owner = desktop.create(FakeWindow::new("Owner", "owner", rect(0, 0, 10, 10)));
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+        desktop.set_owner(child.hwnd, owner.hwnd).unwrap();
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .bind()
+            .unwrap();
+        assert_eq!(desktop.owner(child.hwnd), target.hwnd);
+
+        session.detach();
+        assert_eq!(desktop.owner(child.hwnd), owner.hwnd);
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(0, 0, 200, 300));
+
+        // the hook is gone, target moves are ignored
+        desktop.move_to(target.hwnd, rect(300, 300, 400, 400));
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(0, 0, 200, 300));
+    }
+
+    #[test]
+    fn test_session_started_in_background() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .start()
+            .unwrap();
+
+        desktop.move_to(target.hwnd, rect(200, 100, 400, 400));
+        for _ in 0..500 {
+            if desktop.rect(child.hwnd).unwrap().x == 600 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(600, 100, 200, 300));
+
+        drop(session);
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(0, 0, 200, 300));
+    }
+
+    #[test]
+    fn test_attach_coalesce_moves() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let clock = Arc::new(ManualClock::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(0, 0, 100, 100)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 50, 50)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .coalesce(Duration::from_millis(16))
+            .clock(clock.clone())
+            .bind()
+            .unwrap();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(100, 0, 50, 50));
+
+        // a flood in the same frame, only the first one moves at once
+        clock.advance(Duration::from_millis(20));
+        for x in 1..=50 {
+            desktop.move_to(target.hwnd, rect(x, 0, 100, 100));
+            session.pump();
+        }
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(101, 0, 50, 50));
+
+        // not due yet
+        clock.advance(Duration::from_millis(10));
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(101, 0, 50, 50));
+
+        // the kept one ends at the final position
+        clock.advance(Duration::from_millis(6));
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(150, 0, 50, 50));
+    }
+
+    #[test]
+    fn test_attach_drag_end_flushes() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let clock = Arc::new(ManualClock::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(0, 0, 100, 100)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 50, 50)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .clock(clock.clone())
+            .bind()
+            .unwrap();
+
+        let rects: Vec<Rect> = (1..=30).map(|y| rect(0, y, 100, 100)).collect();
+        desktop.drag(target.hwnd, &rects);
+        session.pump();
+
+        // no time passed, still at the final place
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(100, 30, 50, 50));
+    }
+
+    #[test]
+    fn test_group_stacks_companions() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let toolbar = desktop.create(FakeWindow::new("Toolbar", "toolbar", rect(0, 0, 40, 300)));
+        let chat = desktop.create(FakeWindow::new("Chat", "chat", rect(0, 0, 200, 150)));
+        let notes = desktop.create(FakeWindow::new("Notes", "notes", rect(0, 0, 200, 100)));
+
+        let right = AttachOptions { dir: AttachDirection::RightTop, ..Default::default() };
+        let session = AttachGroup::with_system(target, desktop.clone())
+            .add(toolbar, AttachOptions { dir: AttachDirection::LeftTop, ..Default::default() })
+            .add(chat, right)
+            .add(notes, right)
+            .spacing(8)
+            .bind()
+            .unwrap();
+        assert_eq!(session.len(), 3);
+
+        // the second right panel is below the first one
+        assert_eq!(desktop.rect(toolbar.hwnd).unwrap(), rect(60, 100, 40, 300));
+        assert_eq!(desktop.rect(chat.hwnd).unwrap(), rect(500, 100, 200, 150));
+        assert_eq!(desktop.rect(notes.hwnd).unwrap(), rect(500, 258, 200, 100));
+
+        // all follow with one hook
+        desktop.move_to(target.hwnd, rect(200, 50, 400, 400));
+        session.pump();
+        assert_eq!(desktop.rect(toolbar.hwnd).unwrap(), rect(160, 50, 40, 300));
+        assert_eq!(desktop.rect(chat.hwnd).unwrap(), rect(600, 50, 200, 150));
+        assert_eq!(desktop.rect(notes.hwnd).unwrap(), rect(600, 208, 200, 100));
+        assert_eq!(session.current_rect_of(2), Some(rect(600, 208, 200, 100)));
+
+        desktop.set_visible(target.hwnd, false);
+        session.pump();
+        assert!(!desktop.is_visible(toolbar.hwnd));
+        assert!(!desktop.is_visible(notes.hwnd));
+        desktop.set_visible(target.hwnd, true);
+        session.pump();
+        assert!(desktop.is_visible(notes.hwnd));
+
+        // a companion moved to another edge leaves the stack
+        session.set_options_of(1, AttachOptions { dir: AttachDirection::BottomLeft, ..Default::default() });
+        assert_eq!(desktop.rect(chat.hwnd).unwrap(), rect(200, 450, 200, 150));
+        assert_eq!(desktop.rect(notes.hwnd).unwrap(), rect(600, 50, 200, 100));
+        assert_eq!(session.options_of(1).unwrap().dir, AttachDirection::BottomLeft);
+        assert_eq!(session.options_of(3), None);
+
+        session.detach();
+        assert_eq!(desktop.rect(chat.hwnd).unwrap(), rect(0, 0, 200, 150));
+        assert_eq!(desktop.owner(notes.hwnd), HWND(0));
+    }
+
+    #[test]
+    fn test_group_stack_offsets() {
+        use AttachDirection::*;
+
+        let target = rect(100, 100, 400, 400);
+        let size = rect(0, 0, 50, 30);
+        let cases = [
+            (RightTop, rect(500, 135, 50, 30)),
+            (LeftTop, rect(50, 135, 50, 30)),
+            (RightBottom, rect(500, 435, 50, 30)),
+            (TopLeft, rect(155, 70, 50, 30)),
+            (TopRight, rect(395, 70, 50, 30)),
+            (BottomLeft, rect(155, 500, 50, 30)),
+        ];
+
+        for (dir, expected) in cases.iter() {
+            let stack = dir.stack_offset(size.size(), 5);
+            assert_eq!(Rect::from_point_size(dir.apply(size, target, stack), size.size()), *expected, "{:?}", dir);
+        }
+    }
+
+    #[test]
+    fn test_group_needs_companions() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        assert!(AttachGroup::with_system(target, desktop.clone()).bind().is_err());
+    }
+
+    #[test]
+    fn test_attach_inside() {
+        use AttachDirection::*;
+
+        let desktop = Arc::new(FakeDesktop::new());
+        // 8px border, 30px caption
+        let frame = Insets::new(8, 30, 8, 8);
+        let target = desktop.create(FakeWindow { frame, ..FakeWindow::new("Target", "target", rect(100, 100, 416, 338)) });
+        let badge = desktop.create(FakeWindow::new("Badge", "badge", rect(0, 0, 40, 20)));
+
+        let session = WindowAttach::with_system(badge, target, desktop.clone())
+            .dir(RightTop)
+            .mode(AttachMode::Inside(Insets::uniform(10)))
+            .bind()
+            .unwrap();
+
+        // client is (108, 130, 400, 300), pinned 10px inside its right top
+        assert_eq!(desktop.rect(badge.hwnd).unwrap(), rect(458, 140, 40, 20));
+
+        let cases = [
+            (LeftTop, rect(118, 140, 40, 20)),
+            (BottomRight, rect(458, 400, 40, 20)),
+            (LeftBottom, rect(118, 400, 40, 20)),
+            (BottomCenter, rect(288, 400, 40, 20)),
+            (Anchor(super::Anchor::new((0.5, 0.5), (0.0, 0.0))), rect(308, 280, 40, 20)),
+        ];
+        for (dir, expected) in cases.iter() {
+            session.set_dir(*dir);
+            assert_eq!(desktop.rect(badge.hwnd).unwrap(), *expected, "{:?}", dir);
+        }
+
+        // tracks resizes of the target
+        session.set_dir(RightBottom);
+        desktop.move_to(target.hwnd, rect(100, 100, 616, 538));
+        session.pump();
+        assert_eq!(desktop.rect(badge.hwnd).unwrap(), rect(658, 600, 40, 20));
+    }
+
+    #[test]
+    fn test_attach_fill_click_through() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let frame = Insets::new(8, 30, 8, 8);
+        let target = desktop.create(FakeWindow { frame, ..FakeWindow::new("Target", "target", rect(100, 100, 416, 338)) });
+        let hud = desktop.create(FakeWindow::new("Hud", "hud", rect(0, 0, 40, 20)));
+        desktop.set_scale(target.hwnd, ScaleFactor(2.0));
+
+        let session = WindowAttach::with_system(hud, target, desktop.clone())
+            .mode(AttachMode::Fill(Insets::new(0, 10, 0, 0)))
+            .unit(Unit::Logical)
+            .click_through(true)
+            .bind()
+            .unwrap();
+
+        // 10 logical px at 200% below the top of the client area
+        assert_eq!(desktop.rect(hud.hwnd).unwrap(), rect(108, 150, 400, 280));
+        assert!(desktop.get(hud.hwnd).unwrap().click_through);
+
+        desktop.drag(target.hwnd, &[rect(0, 0, 416, 338), rect(0, 0, 216, 138)]);
+        session.pump();
+        assert_eq!(desktop.rect(hud.hwnd).unwrap(), rect(8, 50, 200, 80));
+
+        session.detach();
+        assert!(!desktop.get(hud.hwnd).unwrap().click_through);
+    }
+
+    #[test]
+    fn test_companion_action() {
+        use WindowState::*;
+        use CompanionAction::*;
+
+        let cases = [
+            (Normal, Normal, Nothing),
+            (Normal, Minimized, Hide),
+            (Maximized, Hidden, Hide),
+            (Normal, Cloaked, Hide),
+            (Normal, Gone, Hide),
+            (Minimized, Normal, Show),
+            (Cloaked, Maximized, Show),
+            (Normal, Maximized, Reflow),
+            (Maximized, Normal, Reflow),
+            (Minimized, Hidden, Nothing),
+            (Minimized, Minimized, Nothing),
+        ];
+
+        for (old, new, action) in cases.iter() {
+            assert_eq!(CompanionAction::derive(*old, *new), *action, "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn test_attach_follow_target_state() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .dir(AttachDirection::LeftTop)
+            .bind()
+            .unwrap();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-100, 100, 200, 300));
+
+        // minimized windows are moved far away, don't follow
+        desktop.minimize(target.hwnd);
+        desktop.move_to(target.hwnd, rect(-32000, -32000, 160, 28));
+        session.pump();
+        assert!(!desktop.is_visible(child.hwnd));
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-100, 100, 200, 300));
+
+        desktop.restore(target.hwnd, rect(300, 100, 400, 400));
+        session.pump();
+        assert!(desktop.is_visible(child.hwnd));
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(100, 100, 200, 300));
+
+        // maximized to the work area, flows to the new place
+        desktop.maximize(target.hwnd, rect(0, 0, 1920, 1040));
+        session.pump();
+        assert_eq!(desktop.rect(child.hwnd).unwrap(), rect(-200, 0, 200, 300));
+
+        // another virtual desktop
+        desktop.cloak(target.hwnd, true);
+        session.pump();
+        assert!(!desktop.is_visible(child.hwnd));
+        desktop.cloak(target.hwnd, false);
+        session.pump();
+        assert!(desktop.is_visible(child.hwnd));
+
+        desktop.destroy(target.hwnd);
+        session.pump();
+        assert!(!desktop.is_visible(child.hwnd));
+    }
+
+    #[test]
+    fn test_attach_bind_to_minimized() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+        desktop.minimize(target.hwnd);
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .bind()
+            .unwrap();
+        assert!(!desktop.is_visible(child.hwnd));
+        assert_eq!(session.current_rect(), None);
+
+        desktop.restore(target.hwnd, rect(100, 100, 400, 400));
+        session.pump();
+        assert!(desktop.is_visible(child.hwnd));
+        assert_eq!(session.current_rect(), Some(rect(500, 100, 200, 300)));
+    }
+
+    #[test]
+    fn test_attach_z_order() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", rect(100, 100, 400, 400)));
+        let child = desktop.create(FakeWindow::new("Child", "child", rect(0, 0, 200, 300)));
+        let other = desktop.create(FakeWindow::new("Other", "other", rect(0, 0, 300, 300)));
+
+        let session = WindowAttach::with_system(child, target, desktop.clone())
+            .bind()
+            .unwrap();
+
+        // right above the target, under the other window
+        assert_eq!(desktop.z_order(), vec![other.hwnd, child.hwnd, target.hwnd]);
+        assert!(!desktop.get(child.hwnd).unwrap().topmost);
+
+        desktop.bring_to_front(target.hwnd);
+        session.pump();
+        assert_eq!(desktop.z_order(), vec![child.hwnd, target.hwnd, other.hwnd]);
+
+        desktop.bring_to_front(other.hwnd);
+        desktop.move_to(target.hwnd, rect(200, 100, 400, 400));
+        session.pump();
+        assert_eq!(desktop.z_order(), vec![other.hwnd, child.hwnd, target.hwnd]);
+        assert!(!desktop.get(child.hwnd).unwrap().topmost);
+    }
+
+    #[test]
+    fn test_demo() {
+        let child = Window::from_name(None, "MINGW64:/d/Zoe").unwrap();
+        let target = Window::from_name(None, "MINGW64:/c/Users/Zoe").unwrap();
+        let target = Window::from_name(Some("WeChatMainWndForPC"), "微信").unwrap(); 
+
+        let _session = child.attach_to(target)
+            .match_size(true)
+            .dir(AttachDirection::RightTop)
+            .match_size_min(200)
+            .match_size_max(800)
+            .fix_pos(Offset::new(-10, 0))
+            .start()
+            .unwrap();
+
+        MessageLoop::start(10, |_| { true });
     }
 }
\ No newline at end of file
@@ -0,0 +1,379 @@
+use crate::win2::{backend::{Win32System, WindowSystem}, error::Result, event_filter::EventFilter, point::Offset, rect::{Edge, Rect}, window::Window, window_event::{WinEvent, WinEventListener, WinEventType}};
+
+use std::sync::{Arc, Mutex};
+
+/*
+ * the user dragging a window, as gestures instead of raw events:
+ * started, dragging with the rects and deltas, ended.
+ *
+ * Whether it's moved or resized, and from which edges, is classified
+ * from the rect at the start and the rects of the location changes.
+ */
+
+// what the user does with the window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DragKind {
+    // nothing changed yet, e.g. only clicked on the caption
+    Unknown,
+    Move,
+    // the edges that moved, `[Left]` from the left edge,
+    // `[Right, Bottom]` from the right bottom corner
+    Resize(Vec<Edge>),
+}
+
+// how the rect changed from `start`
+pub fn classify(start: Rect, rect: Rect) -> DragKind {
+    let moved = Edge::ALL.iter()
+        .copied()
+        .filter(|e| start.edge(*e) != rect.edge(*e))
+        .collect::<Vec<_>>();
+
+    if moved.is_empty() {
+        DragKind::Unknown
+    } else if start.size() == rect.size() {
+        DragKind::Move
+    } else {
+        DragKind::Resize(moved)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragPhase {
+    Started,
+    Dragging,
+    Ended,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DragEvent {
+    pub phase: DragPhase,
+    pub window: Window,
+    pub kind: DragKind,
+    // when started and now
+    pub start: Rect,
+    pub rect: Rect,
+    // of the left top, from the start and from the last event
+    pub delta: Offset,
+    pub step: Offset,
+}
+
+impl DragEvent {
+    // how far the edge moved from the start
+    pub fn edge_delta(&self, edge: Edge) -> i32 {
+        self.rect.edge(edge) - self.start.edge(edge)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Drag {
+    window: Window,
+    kind: DragKind,
+    start: Rect,
+    last: Rect,
+}
+
+// turns the rects of a window into drag events, without any hook.
+// One drag at a time, like the move/size loop of Windows.
+#[derive(Debug, Default)]
+pub struct DragTracker {
+    drag: Option<Drag>,
+}
+
+impl DragTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the window being dragged
+    pub fn dragging(&self) -> Option<Window> {
+        self.drag.as_ref().map(|d| d.window)
+    }
+
+    pub fn start(&mut self, w: Window, rect: Rect) -> DragEvent {
+        let drag = Drag { window: w, kind: DragKind::Unknown, start: rect, last: rect };
+        let evt = Self::event(DragPhase::Started, &drag, rect);
+        self.drag = Some(drag);
+        evt
+    }
+
+    // none for other windows, or when the rect didn't change
+    pub fn moved(&mut self, w: Window, rect: Rect) -> Option<DragEvent> {
+        let drag = match self.drag.as_mut() {
+            Some(drag) if drag.window == w && drag.last != rect => drag,
+            _ => return None,
+        };
+
+        // back where it started, it's still what it was
+        let kind = classify(drag.start, rect);
+        if kind != DragKind::Unknown {
+            drag.kind = kind;
+        }
+
+        let evt = Self::event(DragPhase::Dragging, drag, rect);
+        drag.last = rect;
+        Some(evt)
+    }
+
+    // `rect` is none when the window is gone, the last one is used
+    pub fn end(&mut self, w: Window, rect: Option<Rect>) -> Option<DragEvent> {
+        if self.dragging() != Some(w) {
+            return None;
+        }
+
+        // the last location change may come after the end
+        let mut drag = self.drag.take()?;
+        let rect = rect.unwrap_or(drag.last);
+        let kind = classify(drag.start, rect);
+        if kind != DragKind::Unknown {
+            drag.kind = kind;
+        }
+
+        Some(Self::event(DragPhase::Ended, &drag, rect))
+    }
+
+    fn event(phase: DragPhase, drag: &Drag, rect: Rect) -> DragEvent {
+        DragEvent {
+            phase,
+            window: drag.window,
+            kind: drag.kind.clone(),
+            start: drag.start,
+            rect,
+            delta: rect.position() - drag.start.position(),
+            step: rect.position() - drag.last.position(),
+        }
+    }
+}
+
+type DragCallback = Arc<dyn Fn(&DragEvent) + Send + Sync>;
+
+pub struct WindowDrag {
+    w: Window,
+    callbacks: Vec<DragCallback>,
+
+    sys: Arc<dyn WindowSystem>,
+}
+
+impl WindowDrag {
+
+    // drags of `w`, or of all windows for `Window::default()`
+    pub fn new(w: Window) -> Self {
+        Self::with_system(w, Win32System::shared())
+    }
+
+    pub fn with_system(w: Window, sys: Arc<dyn WindowSystem>) -> Self {
+        Self { w, callbacks: vec![], sys }
+    }
+
+    pub fn on_drag<F>(&mut self, cb: F) -> &mut Self
+    where
+        F: Fn(&DragEvent) + Send + Sync + 'static
+    {
+        self.callbacks.push(Arc::new(cb));
+        self
+    }
+
+    // watch in a background thread until the session is dropped
+    pub fn start(&mut self) -> Result<DragSession> {
+        let mut session = self.bind()?;
+        session.listener.start(false)?;
+
+        Ok(session)
+    }
+
+    // install the hook, events are processed by `pump` of the session
+    pub fn bind(&mut self) -> Result<DragSession> {
+        let state = Arc::new(DragState {
+            tracker: Mutex::new(DragTracker::new()),
+            callbacks: self.callbacks.clone(),
+            sys: self.sys.clone(),
+        });
+
+        let (_start, _moved, _end, _destroy) = (state.clone(), state.clone(), state.clone(), state.clone());
+        let mut listener = WinEventListener::with_system(self.w, self.sys.clone());
+        listener.filter(EventFilter::WindowObject);
+        listener.on(WinEventType::MoveResizeStart, move |evt: &WinEvent| _start.start(evt.window));
+        listener.on(WinEventType::LocationChange, move |evt: &WinEvent| _moved.moved(evt.window));
+        listener.on(WinEventType::MoveResizeEnd, move |evt: &WinEvent| _end.end(evt.window));
+        listener.on(WinEventType::Destroy, move |evt: &WinEvent| _destroy.end(evt.window));
+        listener.install()?;
+
+        Ok(DragSession { state, listener })
+    }
+}
+
+struct DragState {
+    tracker: Mutex<DragTracker>,
+    callbacks: Vec<DragCallback>,
+
+    sys: Arc<dyn WindowSystem>,
+}
+
+impl DragState {
+    fn start(&self, w: Window) {
+        if let Ok(rect) = self.sys.rect(w.hwnd) {
+            let evt = self.tracker.lock().unwrap().start(w, rect);
+            self.emit(&evt);
+        }
+    }
+
+    fn moved(&self, w: Window) {
+        let rect = match self.sys.rect(w.hwnd) {
+            Ok(rect) => rect,
+            Err(_) => return,
+        };
+
+        let evt = self.tracker.lock().unwrap().moved(w, rect);
+        if let Some(evt) = evt {
+            self.emit(&evt);
+        }
+    }
+
+    fn end(&self, w: Window) {
+        let rect = self.sys.rect(w.hwnd).ok();
+        let evt = self.tracker.lock().unwrap().end(w, rect);
+        if let Some(evt) = evt {
+            self.emit(&evt);
+        }
+    }
+
+    // not locked, callbacks may move windows
+    fn emit(&self, evt: &DragEvent) {
+        for cb in self.callbacks.iter() {
+            cb(evt);
+        }
+    }
+}
+
+// handle of watched drags, dropping it stops it
+pub struct DragSession {
+    state: Arc<DragState>,
+    listener: WinEventListener,
+}
+
+impl DragSession {
+
+    // the window being dragged now
+    pub fn dragging(&self) -> Option<Window> {
+        self.state.tracker.lock().unwrap().dragging()
+    }
+
+    // process queued events on current thread, for sessions from `bind`
+    pub fn pump(&self) -> usize {
+        self.listener.pump()
+    }
+
+    // stop watching, same as dropping the session
+    pub fn detach(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bindings::Windows::Win32::{Foundation::HWND, UI::WindowsAndMessaging::{EVENT_SYSTEM_MOVESIZEEND, EVENT_SYSTEM_MOVESIZESTART}};
+
+    use crate::win2::{fake::{FakeDesktop, FakeWindow}, point::Offset, rect::{Edge, Rect}, window::Window};
+
+    use super::{classify, DragKind, DragPhase, DragTracker, WindowDrag};
+
+    #[test]
+    fn test_classify() {
+        use Edge::*;
+        let start = Rect::new(100, 100, 200, 100);
+
+        let cases = [
+            (Rect::new(100, 100, 200, 100), DragKind::Unknown),
+            (Rect::new(130, 90, 200, 100), DragKind::Move),
+            (Rect::new(-500, 100, 200, 100), DragKind::Move),
+            (Rect::new(80, 100, 220, 100), DragKind::Resize(vec![Left])),
+            (Rect::new(100, 100, 250, 100), DragKind::Resize(vec![Right])),
+            (Rect::new(100, 90, 200, 110), DragKind::Resize(vec![Top])),
+            (Rect::new(100, 100, 150, 60), DragKind::Resize(vec![Right, Bottom])),
+            (Rect::new(110, 120, 190, 80), DragKind::Resize(vec![Left, Top])),
+            // moved and resized at once, e.g. snapped to half the screen
+            (Rect::new(0, 0, 960, 1080), DragKind::Resize(vec![Left, Top, Right, Bottom])),
+        ];
+
+        for (rect, expected) in cases.iter() {
+            assert_eq!(classify(start, *rect), *expected, "{:?}", rect);
+        }
+    }
+
+    #[test]
+    fn test_drag_tracker() {
+        let (w, other) = (Window::default(), Window { hwnd: HWND(7), pid: 0, tid: 0 });
+        let mut tracker = DragTracker::new();
+        assert_eq!(tracker.moved(w, Rect::new(0, 0, 10, 10)), None);
+
+        let started = tracker.start(w, Rect::new(100, 100, 200, 100));
+        assert_eq!((started.phase, started.kind, started.delta), (DragPhase::Started, DragKind::Unknown, Offset::zero()));
+
+        // from the left edge, the other window or an unchanged rect don't count
+        let evt = tracker.moved(w, Rect::new(90, 100, 210, 100)).unwrap();
+        assert_eq!((evt.phase, evt.kind.clone()), (DragPhase::Dragging, DragKind::Resize(vec![Edge::Left])));
+        assert_eq!((evt.delta, evt.edge_delta(Edge::Left), evt.edge_delta(Edge::Right)), (Offset::new(-10, 0), -10, 0));
+        assert_eq!(tracker.moved(other, Rect::new(0, 0, 10, 10)), None);
+        assert_eq!(tracker.moved(w, Rect::new(90, 100, 210, 100)), None);
+
+        let evt = tracker.moved(w, Rect::new(70, 100, 230, 100)).unwrap();
+        assert_eq!((evt.delta, evt.step), (Offset::new(-30, 0), Offset::new(-20, 0)));
+
+        // back at the start it's still a resize
+        let evt = tracker.moved(w, Rect::new(100, 100, 200, 100)).unwrap();
+        assert_eq!(evt.kind, DragKind::Resize(vec![Edge::Left]));
+
+        assert_eq!(tracker.end(other, None), None);
+        let ended = tracker.end(w, None).unwrap();
+        assert_eq!((ended.phase, ended.rect, tracker.dragging()), (DragPhase::Ended, Rect::new(100, 100, 200, 100), None));
+        assert_eq!(tracker.end(w, None), None);
+    }
+
+    #[test]
+    fn test_window_drag() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(100, 100, 400, 300)));
+        let other = desktop.create(FakeWindow::new("Other", "other", Rect::new(0, 0, 100, 100)));
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let _events = events.clone();
+        let session = WindowDrag::with_system(target, desktop.clone())
+            .on_drag(move |evt| _events.lock().unwrap().push((evt.phase, evt.kind.clone(), evt.delta)))
+            .bind()
+            .unwrap();
+
+        // routed as they happen, rects are read when handled
+        let drag = |w: Window, rects: &[Rect]| {
+            desktop.emit(EVENT_SYSTEM_MOVESIZESTART, w.hwnd);
+            session.pump();
+            for r in rects {
+                desktop.move_to(w.hwnd, *r);
+                session.pump();
+            }
+            desktop.emit(EVENT_SYSTEM_MOVESIZEEND, w.hwnd);
+            session.pump();
+        };
+
+        drag(target, &[Rect::new(110, 100, 400, 300), Rect::new(130, 90, 400, 300)]);
+        drag(other, &[Rect::new(10, 10, 100, 100)]);
+        desktop.move_to(target.hwnd, Rect::new(0, 0, 400, 300));
+        session.pump();
+        assert_eq!(*events.lock().unwrap(), vec![
+            (DragPhase::Started, DragKind::Unknown, Offset::zero()),
+            (DragPhase::Dragging, DragKind::Move, Offset::new(10, 0)),
+            (DragPhase::Dragging, DragKind::Move, Offset::new(30, -10)),
+            (DragPhase::Ended, DragKind::Move, Offset::new(30, -10)),
+        ]);
+
+        // resized from the right bottom corner, then closed while dragged
+        events.lock().unwrap().clear();
+        drag(target, &[Rect::new(0, 0, 450, 320)]);
+        assert_eq!(events.lock().unwrap()[1].1, DragKind::Resize(vec![Edge::Right, Edge::Bottom]));
+
+        desktop.emit(EVENT_SYSTEM_MOVESIZESTART, target.hwnd);
+        session.pump();
+        assert_eq!(session.dragging(), Some(target));
+        desktop.destroy(target.hwnd);
+        session.pump();
+        assert_eq!((session.dragging(), events.lock().unwrap().last().unwrap().0), (None, DragPhase::Ended));
+    }
+}
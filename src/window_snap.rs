@@ -0,0 +1,479 @@
+use crate::win2::{backend::{Win32System, WindowSystem}, error::Result, point::{Offset, Size}, rect::{Edge, Rect}, window::Window, event_filter::EventFilter, window_event::{WinEvent, WinEventListener, WinEventType}};
+
+use std::sync::{Arc, Mutex};
+
+/*
+ * windows snapping to each other and to the target while dragged,
+ * like the windows of Winamp.
+ *
+ * A window dropped against another one is glued to it and moves
+ * along when that one moves, until it is dragged apart again.
+ * The target is snapped to and followed, but never moved.
+ */
+
+// an edge of the moving rect pulled onto an edge of another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapCandidate {
+    // index in the other rects
+    pub index: usize,
+    // edge of the moving rect
+    pub edge: Edge,
+    // edge of the other rect it goes to
+    pub to: Edge,
+    // move along the axis of the edges
+    pub delta: i32,
+}
+
+impl SnapCandidate {
+    // side by side, not only lined up
+    pub fn is_docked(&self) -> bool {
+        self.edge == self.to.opposite()
+    }
+}
+
+// the moved rect and what it snapped to on each axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snap {
+    pub rect: Rect,
+    pub x: Option<SnapCandidate>,
+    pub y: Option<SnapCandidate>,
+}
+
+// all edges within `distance`, closest first, docking before lining up.
+//
+// Edges only pull when the rects are within `distance` on the other
+// axis too, far away windows on the same line don't count.
+pub fn snap_candidates(moving: Rect, others: &[Rect], distance: i32) -> Vec<SnapCandidate> {
+    let mut candidates = vec![];
+
+    for (index, other) in others.iter().enumerate() {
+        let gap = moving.gap(other);
+        for edge in Edge::ALL.iter().copied() {
+            let across = if edge.on_x_axis() { gap.height } else { gap.width };
+            if across > distance {
+                continue;
+            }
+
+            for to in [edge.opposite(), edge].iter().copied() {
+                let delta = other.edge(to) - moving.edge(edge);
+                if delta.abs() <= distance {
+                    candidates.push(SnapCandidate { index, edge, to, delta });
+                }
+            }
+        }
+    }
+
+    candidates.sort_by_key(|c| (c.delta.abs(), !c.is_docked(), c.index));
+    candidates
+}
+
+// move by the closest candidate on each axis
+pub fn snap_rect(moving: Rect, others: &[Rect], distance: i32) -> Snap {
+    let candidates = snap_candidates(moving, others, distance);
+    let x = candidates.iter().find(|c| c.edge.on_x_axis()).copied();
+    let y = candidates.iter().find(|c| !c.edge.on_x_axis()).copied();
+
+    let offset = Offset::new(x.map_or(0, |c| c.delta), y.map_or(0, |c| c.delta));
+    Snap { rect: moving.translate(offset), x, y }
+}
+
+// the rect `moving` sits flush against, the one sharing the longest
+// part of the edge when there are more
+pub fn docked_to(moving: Rect, others: &[Rect]) -> Option<usize> {
+    let shared = |c: &SnapCandidate| {
+        let other = others[c.index];
+        if c.edge.on_x_axis() {
+            moving.bottom().min(other.bottom()) - moving.top().max(other.top())
+        } else {
+            moving.right().min(other.right()) - moving.left().max(other.left())
+        }
+    };
+
+    snap_candidates(moving, others, 0).iter()
+        .filter(|c| c.is_docked() && shared(c) > 0)
+        .fold(None, |best: Option<(usize, i32)>, c| match best {
+            Some((_, len)) if len >= shared(c) => best,
+            _ => Some((c.index, shared(c))),
+        })
+        .map(|(index, _)| index)
+}
+
+pub struct WindowSnap {
+    target: Window,
+    windows: Vec<Window>,
+    distance: i32,
+
+    sys: Arc<dyn WindowSystem>,
+}
+
+impl WindowSnap {
+
+    pub fn new(target: Window) -> Self {
+        Self::with_system(target, Win32System::shared())
+    }
+
+    pub fn with_system(target: Window, sys: Arc<dyn WindowSystem>) -> Self {
+        Self {
+            target,
+            windows: vec![],
+            distance: 10,
+            sys,
+        }
+    }
+
+    // a window that snaps and can be glued
+    pub fn add(&mut self, w: Window) -> &mut Self {
+        self.windows.push(w);
+        self
+    }
+
+    // how close edges pull, in pixels
+    pub fn distance(&mut self, px: i32) -> &mut Self {
+        self.distance = px;
+        self
+    }
+
+    // snap in a background thread until the session is dropped
+    pub fn start(&mut self) -> Result<SnapSession> {
+        let mut session = self.bind()?;
+        session.listener.start(false)?;
+
+        Ok(session)
+    }
+
+    // glue the windows already touching and install the hook,
+    // events are processed by `pump` of the session.
+    pub fn bind(&mut self) -> Result<SnapSession> {
+        let state = Arc::new(SnapState {
+            target: self.target,
+            windows: self.windows.clone(),
+            distance: self.distance,
+            sys: self.sys.clone(),
+            dragging: Mutex::new(None),
+            glue: Mutex::new(vec![]),
+        });
+
+        for w in state.windows.iter() {
+            state.glue(*w);
+        }
+
+        // hwnd 0 gets the events of all windows
+        let (_start, _moved, _end, _destroy) = (state.clone(), state.clone(), state.clone(), state.clone());
+        let mut listener = WinEventListener::with_system(Window::default(), self.sys.clone());
+        listener.filter(EventFilter::WindowObject);
+        listener.on(WinEventType::MoveResizeStart, move |evt: &WinEvent| _start.begin(evt.window));
+        listener.on(WinEventType::LocationChange, move |evt: &WinEvent| _moved.moved(evt.window));
+        listener.on(WinEventType::MoveResizeEnd, move |evt: &WinEvent| _end.end(evt.window));
+        listener.on(WinEventType::Destroy, move |evt: &WinEvent| _destroy.forget(evt.window));
+        listener.install()?;
+
+        Ok(SnapSession { state, listener })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Glue {
+    w: Window,
+    to: Window,
+    // from the left top of `to`
+    offset: Offset,
+}
+
+struct SnapState {
+    target: Window,
+    windows: Vec<Window>,
+    distance: i32,
+
+    sys: Arc<dyn WindowSystem>,
+
+    // the window dragged and its size when started,
+    // resizing doesn't snap
+    dragging: Mutex<Option<(Window, Size)>>,
+    glue: Mutex<Vec<Glue>>,
+}
+
+impl SnapState {
+
+    // the windows moving along with `w`, not `w` itself
+    fn followers(&self, w: Window) -> Vec<Window> {
+        let glue = self.glue.lock().unwrap();
+        let mut found = vec![];
+        let mut next = vec![w];
+
+        while let Some(w) = next.pop() {
+            for g in glue.iter().filter(|g| g.to == w) {
+                if !found.contains(&g.w) {
+                    found.push(g.w);
+                    next.push(g.w);
+                }
+            }
+        }
+        found
+    }
+
+    // the shown windows `w` can snap to
+    fn others(&self, w: Window) -> Vec<(Window, Rect)> {
+        let followers = self.followers(w);
+
+        std::iter::once(self.target)
+            .chain(self.windows.iter().copied())
+            .filter(|o| *o != w && !followers.contains(o))
+            .filter(|o| self.sys.window_state(o.hwnd).is_shown())
+            .filter_map(|o| self.sys.rect(o.hwnd).ok().map(|rect| (o, rect)))
+            .collect()
+    }
+
+    fn begin(&self, w: Window) {
+        if !self.windows.contains(&w) {
+            return;
+        }
+
+        if let Ok(rect) = self.sys.rect(w.hwnd) {
+            *self.dragging.lock().unwrap() = Some((w, rect.size()));
+        }
+    }
+
+    fn moved(&self, w: Window) {
+        let mut rect = match self.sys.rect(w.hwnd) {
+            Ok(rect) => rect,
+            Err(_) => return,
+        };
+
+        let dragged = *self.dragging.lock().unwrap() == Some((w, rect.size()));
+        if dragged {
+            let others = self.others(w).into_iter().map(|(_, r)| r).collect::<Vec<_>>();
+            let snap = snap_rect(rect, &others, self.distance);
+            if snap.rect != rect && self.sys.set_rect(w.hwnd, &snap.rect, false).is_ok() {
+                rect = snap.rect;
+            }
+        }
+
+        self.follow(w, rect);
+    }
+
+    // move the windows glued to `w` along
+    fn follow(&self, w: Window, rect: Rect) {
+        let glued = self.glue.lock().unwrap().iter()
+            .filter(|g| g.to == w)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for g in glued {
+            let current = match self.sys.rect(g.w.hwnd) {
+                Ok(rect) => rect,
+                Err(_) => continue,
+            };
+
+            let moved = Rect::from_point_size(rect.position() + g.offset, current.size());
+            if moved != current {
+                let _ = self.sys.set_rect(g.w.hwnd, &moved, false);
+            }
+            self.follow(g.w, moved);
+        }
+    }
+
+    fn end(&self, w: Window) {
+        let mut dragging = self.dragging.lock().unwrap();
+        if !matches!(*dragging, Some((d, _)) if d == w) {
+            return;
+        }
+        dragging.take();
+        drop(dragging);
+
+        // glued where it was dropped, or free
+        self.glue(w);
+    }
+
+    fn glue(&self, w: Window) {
+        self.unglue(w);
+
+        let rect = match self.sys.rect(w.hwnd) {
+            Ok(rect) => rect,
+            Err(_) => return,
+        };
+        let others = self.others(w);
+        let rects = others.iter().map(|(_, r)| *r).collect::<Vec<_>>();
+
+        if let Some(i) = docked_to(rect, &rects) {
+            let (to, to_rect) = others[i];
+            let offset = rect.position() - to_rect.position();
+            self.glue.lock().unwrap().push(Glue { w, to, offset });
+        }
+    }
+
+    fn unglue(&self, w: Window) {
+        self.glue.lock().unwrap().retain(|g| g.w != w);
+    }
+
+    fn forget(&self, w: Window) {
+        self.glue.lock().unwrap().retain(|g| g.w != w && g.to != w);
+    }
+}
+
+// handle of running snapping, dropping it stops it
+pub struct SnapSession {
+    state: Arc<SnapState>,
+    listener: WinEventListener,
+}
+
+impl SnapSession {
+
+    // the window `w` moves along with
+    pub fn glued_to(&self, w: Window) -> Option<Window> {
+        self.state.glue.lock().unwrap().iter().find(|g| g.w == w).map(|g| g.to)
+    }
+
+    // let the window stay where it is when the other one moves
+    pub fn unglue(&self, w: Window) {
+        self.state.unglue(w);
+    }
+
+    // process queued events on current thread, for sessions from `bind`
+    pub fn pump(&self) -> usize {
+        self.listener.pump()
+    }
+
+    // stop snapping, same as dropping the session
+    pub fn detach(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::win2::{backend::WindowSystem, fake::{FakeDesktop, FakeWindow}, rect::{Edge, Rect}};
+
+    use super::{docked_to, snap_candidates, snap_rect, SnapCandidate, WindowSnap};
+
+    #[test]
+    fn test_snap_candidates() {
+        let other = Rect::new(100, 100, 200, 100);
+        let c = |edge: Edge, to: Edge, delta: i32| SnapCandidate { index: 0, edge, to, delta };
+
+        let cases = [
+            // 4px right of the right edge, tops 3px apart
+            (Rect::new(304, 103, 50, 50), vec![c(Edge::Top, Edge::Top, -3), c(Edge::Left, Edge::Right, -4)]),
+            // overlapping the left edge by 2px, bottoms lined up
+            (Rect::new(52, 150, 50, 50), vec![c(Edge::Bottom, Edge::Bottom, 0), c(Edge::Right, Edge::Left, -2)]),
+            // just above, the docking wins over lining up at the same distance
+            (Rect::new(150, 45, 50, 50), vec![c(Edge::Bottom, Edge::Top, 5)]),
+            (Rect::new(95, 45, 10, 50), vec![c(Edge::Right, Edge::Left, -5), c(Edge::Bottom, Edge::Top, 5), c(Edge::Left, Edge::Left, 5)]),
+            // too far away
+            (Rect::new(320, 100, 50, 50), vec![]),
+            // on the same line but far below
+            (Rect::new(300, 400, 50, 50), vec![]),
+        ];
+
+        for (moving, expected) in cases.iter() {
+            assert_eq!(snap_candidates(*moving, &[other], 10), *expected, "{:?}", moving);
+        }
+
+        // the closest edge on each axis wins, from any rect
+        let near = Rect::new(100, 300, 204, 100);
+        let snap = snap_rect(Rect::new(306, 198, 50, 97), &[other, near], 10);
+        assert_eq!(snap.x, Some(SnapCandidate { index: 1, edge: Edge::Left, to: Edge::Right, delta: -2 }));
+        assert_eq!(snap.y, Some(SnapCandidate { index: 0, edge: Edge::Top, to: Edge::Bottom, delta: 2 }));
+        assert_eq!(snap.rect, Rect::new(304, 200, 50, 97));
+    }
+
+    #[test]
+    fn test_snap_rect() {
+        let others = [Rect::new(100, 100, 200, 100), Rect::new(100, 300, 200, 100)];
+
+        let cases = [
+            // into the corner right of the first one
+            (Rect::new(306, 97, 50, 50), Rect::new(300, 100, 50, 50)),
+            // between the two, docked to the first one and lined up
+            (Rect::new(95, 205, 80, 90), Rect::new(100, 200, 80, 90)),
+            // nothing near
+            (Rect::new(500, 500, 50, 50), Rect::new(500, 500, 50, 50)),
+        ];
+
+        for (moving, expected) in cases.iter() {
+            let snap = snap_rect(*moving, &others, 10);
+            assert_eq!(snap.rect, *expected, "{:?}", moving);
+            assert!(snap_rect(snap.rect, &others, 10).rect == snap.rect, "snapping again keeps it");
+        }
+
+        assert_eq!(docked_to(Rect::new(300, 100, 50, 50), &others), Some(0));
+        assert_eq!(docked_to(Rect::new(100, 400, 50, 50), &others), Some(1));
+        // lined up or only touching corners isn't docked
+        assert_eq!(docked_to(Rect::new(100, 220, 50, 50), &others), None);
+        assert_eq!(docked_to(Rect::new(300, 50, 50, 50), &others), None);
+    }
+
+    #[test]
+    fn test_snap_and_glue() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(100, 100, 400, 300)));
+        let eq = desktop.create(FakeWindow::new("Tool", "eq", Rect::new(800, 100, 200, 100)));
+        let list = desktop.create(FakeWindow::new("Tool", "list", Rect::new(800, 600, 200, 100)));
+
+        let session = WindowSnap::with_system(target, desktop.clone())
+            .add(eq)
+            .add(list)
+            .distance(8)
+            .bind()
+            .unwrap();
+        assert_eq!(session.glued_to(eq), None);
+
+        // dragged close to the right edge of target, pulled on to it
+        desktop.drag(eq.hwnd, &[Rect::new(506, 104, 200, 100)]);
+        session.pump();
+        assert_eq!(desktop.rect(eq.hwnd).unwrap(), Rect::new(500, 100, 200, 100));
+        assert_eq!(session.glued_to(eq), Some(target));
+
+        // below the eq, glued to it and not the target, they share more
+        desktop.drag(list.hwnd, &[Rect::new(505, 203, 200, 100)]);
+        session.pump();
+        assert_eq!(desktop.rect(list.hwnd).unwrap(), Rect::new(500, 200, 200, 100));
+        assert_eq!(session.glued_to(list), Some(eq));
+
+        // both follow the target
+        desktop.drag(target.hwnd, &[Rect::new(0, 50, 400, 300)]);
+        session.pump();
+        assert_eq!(desktop.rect(eq.hwnd).unwrap(), Rect::new(400, 50, 200, 100));
+        assert_eq!(desktop.rect(list.hwnd).unwrap(), Rect::new(400, 150, 200, 100));
+
+        // the list goes along when the eq is dragged, it stays glued
+        desktop.drag(eq.hwnd, &[Rect::new(403, 20, 200, 100)]);
+        session.pump();
+        assert_eq!(desktop.rect(eq.hwnd).unwrap(), Rect::new(400, 20, 200, 100));
+        assert_eq!(desktop.rect(list.hwnd).unwrap(), Rect::new(400, 120, 200, 100));
+        assert_eq!(session.glued_to(eq), Some(target));
+
+        // dragged apart
+        desktop.drag(list.hwnd, &[Rect::new(700, 500, 200, 100)]);
+        session.pump();
+        assert_eq!(session.glued_to(list), None);
+
+        desktop.move_to(target.hwnd, Rect::new(100, 100, 400, 300));
+        session.pump();
+        assert_eq!(desktop.rect(eq.hwnd).unwrap(), Rect::new(500, 70, 200, 100));
+        assert_eq!(desktop.rect(list.hwnd).unwrap(), Rect::new(700, 500, 200, 100));
+
+        // resizing doesn't snap
+        desktop.drag(list.hwnd, &[Rect::new(700, 500, 203, 100)]);
+        session.pump();
+        assert_eq!(desktop.rect(list.hwnd).unwrap(), Rect::new(700, 500, 203, 100));
+
+        desktop.destroy(target.hwnd);
+        session.pump();
+        assert_eq!(session.glued_to(eq), None);
+    }
+
+    #[test]
+    fn test_glued_when_bound() {
+        let desktop = Arc::new(FakeDesktop::new());
+        let target = desktop.create(FakeWindow::new("Target", "target", Rect::new(100, 100, 400, 300)));
+        let tool = desktop.create(FakeWindow::new("Tool", "tool", Rect::new(100, 400, 400, 50)));
+
+        let session = WindowSnap::with_system(target, desktop.clone()).add(tool).bind().unwrap();
+        assert_eq!(session.glued_to(tool), Some(target));
+
+        session.unglue(tool);
+        desktop.move_to(target.hwnd, Rect::new(0, 0, 400, 300));
+        session.pump();
+        assert_eq!(desktop.rect(tool.hwnd).unwrap(), Rect::new(100, 400, 400, 50));
+    }
+}